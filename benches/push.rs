@@ -0,0 +1,90 @@
+//! Compare the wait-free `FixSizedVec::push()`, which reserves its index with
+//! a single `fetch_add()`, against the CAS loop it used to have, and against
+//! the push to a vector that can be waited on.
+//!
+//! Run it with `cargo bench`, every thread keeps pushing until the vector is
+//! full, and the average time per push is reported.
+
+use demystify_boxcar::fix_sized::FixSizedVec;
+use std::{
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const CAPACITY: usize = 1 << 20;
+const ROUNDS: u32 = 10;
+
+/// How indexes are reserved, on a bare counter without touching any slot.
+fn reserve_with_cas_loop(len: &AtomicUsize) -> Option<usize> {
+    loop {
+        let snapshot = len.load(Ordering::Relaxed);
+        if snapshot == CAPACITY {
+            return None;
+        }
+
+        if len
+            .compare_exchange(
+                snapshot,
+                snapshot + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            return Some(snapshot);
+        }
+    }
+}
+
+fn reserve_with_fetch_add(len: &AtomicUsize) -> Option<usize> {
+    let idx = len.fetch_add(1, Ordering::Relaxed);
+    if idx >= CAPACITY {
+        len.fetch_min(CAPACITY, Ordering::Relaxed);
+        return None;
+    }
+
+    Some(idx)
+}
+
+/// Run `push` on `threads` threads until it fails, return the time taken.
+fn run<P: Fn() -> bool + Sync>(threads: usize, push: P) -> Duration {
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| while push() {});
+        }
+    });
+    start.elapsed()
+}
+
+fn report(name: &str, threads: usize, mut bench: impl FnMut() -> Duration) {
+    let total: Duration = (0..ROUNDS).map(|_| bench()).sum();
+    let per_push = total / ROUNDS / CAPACITY as u32;
+    println!("{name:<28} threads: {threads:<2} {per_push:>8.2?}/push");
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8] {
+        report("reserve (CAS loop)", threads, || {
+            let len = AtomicUsize::new(0);
+            run(threads, || black_box(reserve_with_cas_loop(&len)).is_some())
+        });
+        report("reserve (fetch_add)", threads, || {
+            let len = AtomicUsize::new(0);
+            run(threads, || {
+                black_box(reserve_with_fetch_add(&len)).is_some()
+            })
+        });
+        report("FixSizedVec::push()", threads, || {
+            let vec = FixSizedVec::with_capacity(CAPACITY);
+            run(threads, || vec.push(black_box(0_usize)).is_ok())
+        });
+        report("FixSizedVec::push() (WAIT)", threads, || {
+            let vec = FixSizedVec::waitable_with_capacity(CAPACITY);
+            run(threads, || vec.push(black_box(0_usize)).is_ok())
+        });
+        println!();
+    }
+}
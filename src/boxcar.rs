@@ -0,0 +1,567 @@
+use crate::{
+    error::{PushError, PushIfLenError},
+    AppendOnlyVec, Iter,
+};
+use std::{
+    cell::UnsafeCell,
+    fmt::{Debug, Formatter},
+    mem::MaybeUninit,
+    ptr::null_mut,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    vec::Vec as StdVec,
+};
+
+/// `log2()` of the length of the first bucket.
+const FIRST_BUCKET_BITS: usize = 5;
+/// Length of the first bucket, bucket `i` has `FIRST_BUCKET_LEN << i` entries.
+const FIRST_BUCKET_LEN: usize = 1 << FIRST_BUCKET_BITS;
+/// Number of buckets.
+///
+/// With this many buckets, the total capacity is
+/// `FIRST_BUCKET_LEN * (2^BUCKETS - 1)`, so every index that won't overflow
+/// `index + FIRST_BUCKET_LEN` has a place to live.
+const BUCKETS: usize = usize::BITS as usize - FIRST_BUCKET_BITS;
+
+/// An unbounded, lock-free, append-only Vector.
+///
+/// Elements are stored in a list of buckets whose lengths grow geometrically
+/// (32, 64, 128, ...), a bucket is allocated the first time an index that
+/// falls in it gets written. Since buckets never move once allocated, a
+/// reference returned by [`BoxcarVec::get()`] stays valid for as long as the
+/// vector lives, and finding an index is just some bit twiddling, so `get()`
+/// is O(1).
+///
+/// # Thread Safety
+///
+/// It can be shared between threads only if `T: Send + Sync`, as other threads
+/// can push `T`s into it and get `&T`s out of it.
+pub struct BoxcarVec<T> {
+    /// Pointers to the first entry of every bucket, NULL if that bucket has
+    /// not been allocated yet.
+    buckets: [AtomicPtr<Entry<T>>; BUCKETS],
+    /// Length
+    ///
+    /// It also controls which index a thread will write to.
+    len: AtomicUsize,
+}
+
+/// An entry within a bucket.
+///
+/// Same as `FixSizedVec`, the `AtomicBool` flag is used to indicate whether
+/// `slot` has been initialized or not.
+struct Entry<T> {
+    slot: UnsafeCell<MaybeUninit<T>>,
+    active: AtomicBool,
+}
+
+impl<T> Entry<T> {
+    /// Create an uninitialized [`Entry`].
+    fn new() -> Self {
+        Self {
+            slot: UnsafeCell::new(MaybeUninit::uninit()),
+            active: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Where an index lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    /// Which bucket.
+    bucket: usize,
+    /// Length of that bucket.
+    bucket_len: usize,
+    /// Offset within the bucket.
+    offset: usize,
+}
+
+impl Location {
+    /// Compute the location of `index`, return `None` if it is too large to
+    /// be stored.
+    ///
+    /// If we shift every index by `FIRST_BUCKET_LEN`, then the indexes stored
+    /// in bucket `i` are exactly the numbers whose highest set bit is bit
+    /// `i + FIRST_BUCKET_BITS`.
+    fn of(index: usize) -> Option<Self> {
+        let skewed = index.checked_add(FIRST_BUCKET_LEN)?;
+        let bucket = (usize::BITS - 1 - skewed.leading_zeros()) as usize
+            - FIRST_BUCKET_BITS;
+        let bucket_len = FIRST_BUCKET_LEN << bucket;
+
+        Some(Self {
+            bucket,
+            bucket_len,
+            offset: skewed - bucket_len,
+        })
+    }
+}
+
+impl<T: Debug> Debug for BoxcarVec<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let len = self.len();
+        let mut array = StdVec::with_capacity(len);
+        for idx in 0..len {
+            array.push(self.get(idx));
+        }
+
+        f.debug_struct("BoxcarVec")
+            .field("array", &array)
+            .field("len", &len)
+            .finish()
+    }
+}
+
+impl<T> Drop for BoxcarVec<T> {
+    fn drop(&mut self) {
+        for (bucket, ptr) in self.buckets.iter_mut().enumerate() {
+            let ptr = *ptr.get_mut();
+            if ptr.is_null() {
+                continue;
+            }
+
+            let bucket_len = FIRST_BUCKET_LEN << bucket;
+            // SAFETY:
+            // A non-NULL bucket pointer comes from `Box::into_raw()` on a boxed
+            // slice of exactly `bucket_len` entries.
+            let mut entries = unsafe {
+                Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                    ptr, bucket_len,
+                ))
+            };
+
+            for entry in entries.iter_mut() {
+                if *entry.active.get_mut() {
+                    // SAFETY:
+                    // It is guaranteed to be initialized as the `active` flag
+                    // is true, and we have exclusive access.
+                    unsafe { entry.slot.get_mut().assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+// SAFETY:
+//
+// Sending a `BoxcarVec<T>` to another thread moves all the stored `T`s along
+// with it, so `T` has to be `Send`.
+unsafe impl<T: Send> Send for BoxcarVec<T> {}
+
+// SAFETY:
+//
+// With a `&BoxcarVec<T>`, other threads can
+//
+// 1. push values into it, which moves `T`s across threads, so `T: Send` is
+//    needed.
+// 2. get `&T`s out of it, which shares `T`s across threads, so `T: Sync` is
+//    needed.
+//
+// The accesses themselves are synchronized in the same way as `FixSizedVec`:
+// an index is only written by the thread that reserved it, and an entry is
+// only read after its `active` flag is observed to be true.
+unsafe impl<T: Send + Sync> Sync for BoxcarVec<T> {}
+
+impl<T> BoxcarVec<T> {
+    /// Create an empty vector, no bucket will be allocated.
+    ///
+    /// It is a `const fn`, so the vector can be put in a `static` item.
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicPtr::new(null_mut()) }; BUCKETS],
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Push an item to it, return the index it is written to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of pushed items overflows the capacity, which is
+    /// close to `usize::MAX`.
+    pub fn push(&self, val: T) -> usize {
+        self.push_get(val).0
+    }
+
+    /// Same as [`BoxcarVec::push()`], but also return a reference to the
+    /// stored item.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`BoxcarVec::push()`].
+    pub fn push_get(&self, val: T) -> (usize, &T) {
+        let idx = self.len.fetch_add(1, Ordering::Relaxed);
+        // SAFETY:
+        // `idx` is only handed to this thread by the `fetch_add()`.
+        let val = unsafe { self.write(idx, val) };
+
+        (idx, val)
+    }
+
+    /// Push an item only if the length is `expected`, so that it is written
+    /// to index `expected`.
+    ///
+    /// Return [`PushIfLenError::Conflict`] that carries the current length
+    /// and `val` back otherwise.
+    ///
+    /// ```
+    /// use demystify_boxcar::{boxcar::BoxcarVec, error::PushIfLenError};
+    ///
+    /// let vec = BoxcarVec::new();
+    /// assert_eq!(vec.push_if_len(0, "a"), Ok(0));
+    /// assert_eq!(
+    ///     vec.push_if_len(0, "b"),
+    ///     Err(PushIfLenError::Conflict { len: 1, value: "b" })
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Same as [`BoxcarVec::push()`].
+    pub fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        if let Err(len) = self.len.compare_exchange(
+            expected,
+            expected.wrapping_add(1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            return Err(PushIfLenError::Conflict { len, value: val });
+        }
+
+        // SAFETY:
+        // `expected` is only handed to this thread by the CAS.
+        unsafe { self.write(expected, val) };
+
+        Ok(expected)
+    }
+
+    /// Write `val` to `idx` and publish it.
+    ///
+    /// # Safety
+    ///
+    /// `idx` has to be reserved by the caller, and not written yet.
+    unsafe fn write(&self, idx: usize, val: T) -> &T {
+        let location = Location::of(idx).expect("capacity overflow");
+        let bucket = self.get_or_alloc_bucket(location);
+
+        // SAFETY:
+        // `bucket` points to a bucket of `location.bucket_len` entries, and
+        // `location.offset` is smaller than that.
+        let entry = unsafe { &*bucket.add(location.offset) };
+
+        // SAFETY:
+        // `idx` is only handed to this thread, so no one else will access this
+        // slot until the `active` flag is set.
+        let val = unsafe { (*entry.slot.get()).write(val) };
+        entry.active.store(true, Ordering::Release);
+
+        val
+    }
+
+    /// Get the value at `idx`
+    ///
+    /// For uninitialized value, a `None` is returned. Otherwise, return an
+    /// reference to the value.
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let location = Location::of(idx)?;
+        let bucket = self.buckets[location.bucket].load(Ordering::Acquire);
+        if bucket.is_null() {
+            return None;
+        }
+
+        // SAFETY:
+        // `bucket` is a non-NULL pointer to a bucket of `location.bucket_len`
+        // entries, and `location.offset` is smaller than that.
+        let entry = unsafe { &*bucket.add(location.offset) };
+
+        if entry.active.load(Ordering::Acquire) {
+            // SAFETY:
+            // It is guaranteed to be initialized as the `active` flag is true,
+            // the `Acquire` load pairs with the `Release` store in `push()`.
+            Some(unsafe { (*entry.slot.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Return the length.
+    ///
+    /// It is inaccurate due to concurrent appends.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Iterate over the written items.
+    ///
+    /// The length is taken when it gets created, items pushed after that won't
+    /// be visited, and indexes that are reserved but not written yet are
+    /// skipped, so each written item within the length is visited once.
+    pub fn iter(&self) -> Iter<'_, Self> {
+        Iter::new(self)
+    }
+
+    /// Return the bucket `location` lives in, allocate it if it is not there.
+    ///
+    /// Multiple threads can race to allocate the same bucket, only one of them
+    /// wins, the others free what they allocated.
+    fn get_or_alloc_bucket(&self, location: Location) -> *mut Entry<T> {
+        let slot = &self.buckets[location.bucket];
+        let bucket = slot.load(Ordering::Acquire);
+        if !bucket.is_null() {
+            return bucket;
+        }
+
+        let new_bucket = Box::into_raw(
+            (0..location.bucket_len)
+                .map(|_| Entry::new())
+                .collect::<Box<[Entry<T>]>>(),
+        ) as *mut Entry<T>;
+
+        match slot.compare_exchange(
+            null_mut(),
+            new_bucket,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => new_bucket,
+            Err(winner) => {
+                // SAFETY:
+                // `new_bucket` comes from `Box::into_raw()` on a boxed slice of
+                // `location.bucket_len` entries, and it is never published.
+                drop(unsafe {
+                    Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                        new_bucket,
+                        location.bucket_len,
+                    ))
+                });
+                winner
+            }
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BoxcarVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, BoxcarVec<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> AppendOnlyVec for BoxcarVec<T> {
+    type Item = T;
+    type Iter<'a>
+        = Iter<'a, Self>
+    where
+        Self: 'a;
+
+    fn push(&self, val: T) -> Result<usize, PushError<T>> {
+        Ok(BoxcarVec::push(self, val))
+    }
+
+    fn push_get(&self, val: T) -> Result<(usize, &T), PushError<T>> {
+        Ok(BoxcarVec::push_get(self, val))
+    }
+
+    fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        BoxcarVec::push_if_len(self, expected, val)
+    }
+
+    fn get(&self, idx: usize) -> Option<&T> {
+        BoxcarVec::get(self, idx)
+    }
+
+    fn len(&self) -> usize {
+        BoxcarVec::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BoxcarVec::iter(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Counted, DropCounter};
+    use std::{sync::Arc, thread::spawn};
+
+    #[test]
+    fn it_works() {
+        let vec = Arc::new(BoxcarVec::new());
+        let mut handles = Vec::new();
+
+        for thread_id in 0..5 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for _ in 0..500 {
+                        vec.push(thread_id);
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(vec.len(), 2500);
+        let mut counter = [0_usize; 5];
+        for idx in 0..2500 {
+            let num = *vec.get(idx).unwrap();
+            counter[num as usize] += 1;
+        }
+
+        for item in counter {
+            assert_eq!(item, 500);
+        }
+    }
+
+    #[test]
+    fn location() {
+        let at = |bucket, bucket_len, offset| Location {
+            bucket,
+            bucket_len,
+            offset,
+        };
+
+        assert_eq!(Location::of(0), Some(at(0, 32, 0)));
+        assert_eq!(Location::of(31), Some(at(0, 32, 31)));
+        assert_eq!(Location::of(32), Some(at(1, 64, 0)));
+        assert_eq!(Location::of(95), Some(at(1, 64, 63)));
+        assert_eq!(Location::of(96), Some(at(2, 128, 0)));
+        assert_eq!(
+            Location::of(usize::MAX - FIRST_BUCKET_LEN),
+            Some(at(BUCKETS - 1, 1 << (usize::BITS - 1), usize::MAX >> 1))
+        );
+        assert_eq!(Location::of(usize::MAX - FIRST_BUCKET_LEN + 1), None);
+    }
+
+    #[test]
+    fn references_are_stable() {
+        let vec = BoxcarVec::new();
+        let first = vec.get(vec.push(String::from("first"))).unwrap();
+
+        // Force a few more buckets to be allocated
+        for idx in 0..1000 {
+            assert_eq!(vec.push(idx.to_string()), idx + 1);
+        }
+
+        assert_eq!(first, "first");
+        assert_eq!(vec.get(1000).unwrap(), "999");
+        assert_eq!(vec.get(1001), None);
+    }
+
+    #[test]
+    fn drop_across_buckets() {
+        let counter = DropCounter::default();
+        let vec = BoxcarVec::new();
+        // The first two buckets are full, and the third one is partially
+        // filled.
+        for _ in 0..200 {
+            vec.push(counter.item());
+        }
+
+        assert_eq!(Location::of(199).unwrap().bucket, 2);
+        assert_eq!(counter.dropped(), 0);
+        drop(vec);
+        assert_eq!(counter.dropped(), 200);
+    }
+
+    #[test]
+    fn drop_empty() {
+        let vec = BoxcarVec::<Counted>::new();
+        drop(vec);
+    }
+
+    #[test]
+    fn drop_after_concurrent_pushes() {
+        let counter = DropCounter::default();
+        let vec = Arc::new(BoxcarVec::new());
+        let mut handles = Vec::new();
+
+        // Threads race to allocate the same buckets, the losing allocations
+        // hold no values, so nothing is dropped twice.
+        for _ in 0..4 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+                let counter = counter.clone();
+
+                move || {
+                    for _ in 0..250 {
+                        vec.push(counter.item());
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(counter.dropped(), 0);
+        drop(vec);
+        assert_eq!(counter.dropped(), 1000);
+    }
+
+    #[test]
+    fn string_visibility() {
+        const WRITERS: usize = 4;
+        const READERS: usize = 4;
+        const LEN: usize = 1024;
+
+        let vec = Arc::new(BoxcarVec::<String>::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..READERS {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    let mut seen = 0;
+                    while seen < LEN {
+                        seen = 0;
+                        for idx in 0..LEN {
+                            if let Some(val) = vec.get(idx) {
+                                assert_eq!(val.len(), 64);
+                                assert!(val.bytes().all(|b| b == b'x'));
+                                seen += 1;
+                            }
+                        }
+                    }
+                }
+            }));
+        }
+
+        for _ in 0..WRITERS {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for _ in 0..LEN / WRITERS {
+                        vec.push("x".repeat(64));
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
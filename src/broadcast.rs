@@ -0,0 +1,366 @@
+use crate::{
+    boxcar::BoxcarVec,
+    error::{RecvError, TryRecvError},
+    wait::{WaitQueue, WakerSlot},
+};
+use std::{
+    fmt::{Debug, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// Create a broadcast channel, the returned receiver starts from the first
+/// message.
+///
+/// Every message is pushed to a single [`BoxcarVec`] shared by all the senders
+/// and receivers, each receiver keeps its own index into it, so a message is
+/// stored once no matter how many receivers there are, and receivers get
+/// `&T`s rather than clones.
+///
+/// Messages are never removed, so that receivers can subscribe at any time,
+/// they are dropped when the senders and receivers are all gone.
+///
+/// ```
+/// use demystify_boxcar::broadcast;
+///
+/// let (tx, mut rx) = broadcast::channel();
+/// let mut late = tx.subscribe();
+/// tx.send("hello");
+///
+/// std::thread::scope(|s| {
+///     s.spawn(move || assert_eq!(rx.recv(), Ok(&"hello")));
+///     s.spawn(move || assert!(late.try_recv().is_ok()));
+/// });
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        buffer: BoxcarVec::new(),
+        senders: AtomicUsize::new(1),
+        waiting: AtomicBool::new(false),
+        waiters: WaitQueue::new(),
+    });
+    let receiver = Receiver::new(Arc::clone(&shared), 0);
+
+    (Sender { shared }, receiver)
+}
+
+/// State shared by the senders and receivers of a channel.
+struct Shared<T> {
+    /// All the sent messages.
+    buffer: BoxcarVec<T>,
+    /// Number of senders, the channel is disconnected once it reaches 0.
+    senders: AtomicUsize,
+    /// Someone is waiting for a new message, so the next send has to notify
+    /// the waiters, see `WaitQueue`.
+    waiting: AtomicBool,
+    /// Receivers waiting for new messages, they are notified by the sends
+    /// that see `waiting`, and when the last sender is dropped.
+    waiters: WaitQueue,
+}
+
+impl<T> Shared<T> {
+    /// Same as `poll_recv()`, but set `waiting` and check again before
+    /// returning `None`, so that the next send notifies the waiters.
+    fn watch_recv(
+        &self,
+        position: &mut usize,
+    ) -> Option<Result<&T, RecvError>> {
+        self.poll_recv(position).or_else(|| {
+            // `Acquire` so that the message is visible if the send comes
+            // first, see `WaitQueue`.
+            self.waiting.swap(true, Ordering::AcqRel);
+            self.poll_recv(position)
+        })
+    }
+
+    /// Return `None` if the message at `position` may still be sent,
+    /// otherwise return it and move `position` forward, or return
+    /// [`RecvError`] if all the senders are dropped.
+    fn poll_recv(&self, position: &mut usize) -> Option<Result<&T, RecvError>> {
+        if let Some(val) = self.buffer.get(*position) {
+            *position += 1;
+            return Some(Ok(val));
+        }
+
+        if self.senders.load(Ordering::Acquire) != 0 {
+            return None;
+        }
+
+        // The message can be sent between the two loads above, check again
+        // now that no one can send anymore.
+        match self.buffer.get(*position) {
+            Some(val) => {
+                *position += 1;
+                Some(Ok(val))
+            }
+            None => Some(Err(RecvError)),
+        }
+    }
+}
+
+/// The sending half of a broadcast channel, it can be cloned, and shared
+/// between threads.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a message to all the receivers, return its index.
+    pub fn send(&self, val: T) -> usize {
+        let idx = self.shared.buffer.push(val);
+        // See `WaitQueue` for the protocol.
+        if self.shared.waiting.swap(false, Ordering::AcqRel) {
+            self.shared.waiters.notify();
+        }
+        idx
+    }
+
+    /// Create a receiver that only receives the messages sent after this.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let len = self.shared.buffer.len();
+        Receiver::new(Arc::clone(&self.shared), len)
+    }
+
+    /// Create a receiver that receives all the messages, including the ones
+    /// that have been sent.
+    pub fn subscribe_from_head(&self) -> Receiver<T> {
+        Receiver::new(Arc::clone(&self.shared), 0)
+    }
+
+    /// Return the number of messages that have been sent.
+    pub fn len(&self) -> usize {
+        self.shared.buffer.len()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // This `Release` pairs with the `Acquire` load in `Receiver`, so a
+        // receiver that sees no sender also sees every message they sent.
+        if self.shared.senders.fetch_sub(1, Ordering::Release) == 1 {
+            self.shared.waiters.notify();
+        }
+    }
+}
+
+impl<T> Debug for Sender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender").field("len", &self.len()).finish()
+    }
+}
+
+/// The receiving half of a broadcast channel.
+///
+/// It receives the messages in the order of their indexes, starting from the
+/// one it is created with. A clone starts from where the original is.
+///
+/// Receiving moves its index forward, so it takes `&mut self`.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// Index of the message to receive next.
+    position: usize,
+}
+
+impl<T> Receiver<T> {
+    fn new(shared: Arc<Shared<T>>, position: usize) -> Self {
+        Self { shared, position }
+    }
+
+    /// Return the index of the message to receive next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Receive the next message without blocking.
+    ///
+    /// Return [`TryRecvError::Empty`] if it is not sent yet, or
+    /// [`TryRecvError::Disconnected`] if it will never be.
+    pub fn try_recv(&mut self) -> Result<&T, TryRecvError> {
+        match self.shared.poll_recv(&mut self.position) {
+            Some(Ok(val)) => Ok(val),
+            Some(Err(RecvError)) => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Block until the next message is sent, and return it.
+    ///
+    /// Return [`RecvError`] if it will never be, as all the senders are
+    /// dropped.
+    pub fn recv(&mut self) -> Result<&T, RecvError> {
+        let Self { shared, position } = self;
+        // It won't be `None` as there is no deadline.
+        shared
+            .waiters
+            .wait_until(None, || shared.watch_recv(position))
+            .unwrap()
+    }
+
+    /// Return a future that resolves to the next message once it is sent.
+    ///
+    /// Same as [`Receiver::recv()`], it resolves to [`RecvError`] if the
+    /// message will never be sent.
+    pub fn recv_async(&mut self) -> Recv<'_, T> {
+        Recv {
+            shared: &self.shared,
+            position: &mut self.position,
+            waker: WakerSlot::new(&self.shared.waiters),
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self::new(Arc::clone(&self.shared), self.position)
+    }
+}
+
+impl<T> Debug for Receiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver")
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+/// Future returned by [`Receiver::recv_async()`].
+pub struct Recv<'a, T> {
+    shared: &'a Shared<T>,
+    /// Index of the message to receive, borrowed from the receiver.
+    position: &'a mut usize,
+    waker: WakerSlot<'a>,
+}
+
+impl<'a, T> Future for Recv<'a, T> {
+    type Output = Result<&'a T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (shared, position) = (this.shared, &mut *this.position);
+        this.waker.poll_until(cx, || shared.watch_recv(position))
+    }
+}
+
+impl<T> Debug for Recv<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recv")
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wait::tests::block_on;
+    use std::thread::spawn;
+
+    #[test]
+    fn it_works() {
+        let (tx, mut rx) = channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(tx.send(0), 0);
+        assert_eq!(tx.send(1), 1);
+
+        let mut from_tail = tx.subscribe();
+        let mut from_head = tx.subscribe_from_head();
+        assert_eq!(rx.try_recv(), Ok(&0));
+        let mut cloned = rx.clone();
+        assert_eq!(rx.try_recv(), Ok(&1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(cloned.try_recv(), Ok(&1));
+        assert_eq!(from_head.try_recv(), Ok(&0));
+        assert_eq!(from_tail.try_recv(), Err(TryRecvError::Empty));
+
+        tx.send(2);
+        for rx in [&mut rx, &mut cloned, &mut from_tail] {
+            assert_eq!(rx.try_recv(), Ok(&2));
+        }
+        assert_eq!(from_head.position(), 1);
+    }
+
+    #[test]
+    fn disconnect() {
+        let (tx, mut rx) = channel();
+        let tx2 = tx.clone();
+        tx.send(0);
+        drop(tx);
+        tx2.send(1);
+        assert_eq!(rx.try_recv(), Ok(&0));
+
+        drop(tx2);
+        // Messages sent before that are still received.
+        assert_eq!(rx.recv(), Ok(&1));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(block_on(rx.recv_async()), Err(RecvError));
+    }
+
+    #[test]
+    fn fan_out() {
+        let (tx, rx) = channel();
+        let receivers = (0..4)
+            .map(|thread_id| {
+                let mut rx = rx.clone();
+                spawn(move || {
+                    let mut sum = 0;
+                    if thread_id % 2 == 0 {
+                        while let Ok(val) = rx.recv() {
+                            sum += val;
+                        }
+                    } else {
+                        while let Ok(val) = block_on(rx.recv_async()) {
+                            sum += val;
+                        }
+                    }
+                    sum
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(rx);
+
+        let senders = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                spawn(move || {
+                    for i in 0..100 {
+                        tx.send(i);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(tx);
+
+        for sender in senders {
+            sender.join().unwrap();
+        }
+        for receiver in receivers {
+            assert_eq!(receiver.join().unwrap(), 4 * (0..100).sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn receivers_and_futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        fn assert_sync<T: Sync>(_: &T) {}
+
+        let (_tx, mut rx) = channel::<i32>();
+        assert_send(&rx);
+        assert_sync(&rx);
+        assert_send(&rx.recv_async());
+    }
+}
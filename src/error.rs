@@ -0,0 +1,118 @@
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+};
+
+/// Error returned by `push()` when the vector is full.
+///
+/// The rejected value is handed back so that the caller can put it somewhere
+/// else instead of losing it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PushError<T>(pub T);
+
+impl<T> PushError<T> {
+    /// Take the rejected value back.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+// `T` is not required to be `Debug`, same as `std::sync::mpsc::SendError`, so
+// that `unwrap()` works for any `T`.
+impl<T> Debug for PushError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PushError").finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for PushError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("pushing to a full vector")
+    }
+}
+
+impl<T> Error for PushError<T> {}
+
+/// Error returned by `push_if_len()`.
+///
+/// Both variants hand the rejected value back.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PushIfLenError<T> {
+    /// The length is not the expected one, `len` is the length seen instead.
+    Conflict { len: usize, value: T },
+    /// The length is the expected one, but the vector is full.
+    Full(T),
+}
+
+impl<T> PushIfLenError<T> {
+    /// Take the rejected value back.
+    pub fn into_inner(self) -> T {
+        match self {
+            PushIfLenError::Conflict { value, .. } => value,
+            PushIfLenError::Full(value) => value,
+        }
+    }
+}
+
+// Same as `PushError`, `T` is not required to be `Debug`.
+impl<T> Debug for PushIfLenError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushIfLenError::Conflict { len, .. } => f
+                .debug_struct("Conflict")
+                .field("len", len)
+                .finish_non_exhaustive(),
+            PushIfLenError::Full(_) => {
+                f.debug_tuple("Full").finish_non_exhaustive()
+            }
+        }
+    }
+}
+
+impl<T> Display for PushIfLenError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushIfLenError::Conflict { len, .. } => {
+                write!(f, "the length has been changed to {len}")
+            }
+            PushIfLenError::Full(_) => f.write_str("pushing to a full vector"),
+        }
+    }
+}
+
+impl<T> Error for PushIfLenError<T> {}
+
+/// Error returned by `Receiver::recv()` of a broadcast channel, when all the
+/// senders are dropped and every message has been received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl Display for RecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("receiving on a closed channel")
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by `Receiver::try_recv()` of a broadcast channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There is no new message yet.
+    Empty,
+    /// All the senders are dropped, and every message has been received.
+    Disconnected,
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => {
+                f.write_str("receiving on a closed channel")
+            }
+        }
+    }
+}
+
+impl Error for TryRecvError {}
@@ -1,54 +1,207 @@
+use crate::{
+    error::{PushError, PushIfLenError},
+    wait::{WaitQueue, WakerSlot},
+    AppendOnlyVec, Iter,
+};
 use std::{
     cell::UnsafeCell,
     fmt::{Debug, Formatter},
+    future::Future,
+    iter::Zip,
     mem::MaybeUninit,
+    ops::Range,
+    pin::Pin,
     result::Result,
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    slice::IterMut as SliceIterMut,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    task::{Context, Poll},
+    time::{Duration, Instant},
     vec::Vec as StdVec,
 };
 
 /// A fix-sized, lock-free, append-only Vector.
-pub struct FixSizedVec<T, const N: usize> {
-    /// The `AtomicBool` here is used to indicate whether this entry has been
-    /// initialized or not, we cannot use `[Option<T>; N]` here as updating a
-    /// `Option` is not atomic, when reading it with the `get()` method, partially
-    /// initialized memory can be read and causes UB.
-    ///
-    /// There is indeed an `AtomicOption` crate, but it is basically equivalent
-    /// to using an `AtomicBool` flag.
-    array: UnsafeCell<[(MaybeUninit<T>, AtomicBool); N]>,
+///
+/// # Capacity
+///
+/// The capacity is fixed once the vector is created, it can be decided either
+///
+/// 1. at compile time, with `N` and [`FixSizedVec::new()`], the entries are
+///    stored inside the vector, or
+/// 2. at runtime, with [`FixSizedVec::with_capacity()`], the entries are stored
+///    in a boxed slice, `N` is 0 for these vectors.
+///
+/// Large vectors should use the latter, as the former is built on the stack.
+///
+/// ```
+/// use demystify_boxcar::fix_sized::FixSizedVec;
+///
+/// let vec: FixSizedVec<i32> = FixSizedVec::with_capacity(1 << 20);
+/// assert_eq!(vec.capacity(), 1 << 20);
+/// ```
+///
+/// # Waiting
+///
+/// With `WAIT` set to `true`, threads and tasks can wait for the values to be
+/// written, with [`FixSizedVec::wait_for()`] and the like. Every write then
+/// checks for waiters with an RMW rather than a plain store, so it is opt-in,
+/// pushes to a vector that is never waited on don't pay for it.
+///
+/// ```
+/// use demystify_boxcar::fix_sized::FixSizedVec;
+///
+/// let vec = FixSizedVec::waitable_with_capacity(4);
+/// vec.push(1).unwrap();
+/// assert_eq!(vec.wait_for(0), Some(&1));
+/// ```
+///
+/// # Thread Safety
+///
+/// It can be shared between threads only if `T: Send + Sync`, as other threads
+/// can push `T`s into it and get `&T`s out of it.
+pub struct FixSizedVec<T, const N: usize = 0, const WAIT: bool = false> {
+    array: Storage<T, N>,
     /// Length
     ///
-    /// It also controls which index a thread will write to.
+    /// It also controls which index a thread will write to, every push
+    /// reserves an index by incrementing it, so it can exceed the capacity
+    /// when the vector is full, use `len()` to read the clamped value.
     len: AtomicUsize,
+    /// Length of the committed prefix, as far as the readers have seen
+    ///
+    /// Every entry below it is initialized. It is only moved forward by the
+    /// readers, see `committed_len()`, so pushes don't pay for it.
+    committed: AtomicUsize,
+    /// Threads blocked in `wait_for()` or `wait_len_at_least()`, and tasks
+    /// waiting on `wait_for_async()`, they are notified when an entry they
+    /// marked as `WAITING` gets written or abandoned. Only used with `WAIT`.
+    waiters: WaitQueue,
+}
+
+/// A value of the vector, it is initialized once its state is `READY`.
+///
+/// `UnsafeCell<MaybeUninit<T>>` has the same memory layout as `T`, so an array
+/// of them can be viewed as `[T]` once they are all initialized.
+type Value<T> = UnsafeCell<MaybeUninit<T>>;
+
+/// State of an entry: the value is not written yet.
+const EMPTY: u8 = 0;
+/// State of an entry: the value is initialized.
+const READY: u8 = 1;
+/// State of an entry: the index has been reserved, but the value will never be
+/// written, see `FixSizedVec::push_many()`.
+const ABANDONED: u8 = 2;
+/// State of an entry: same as `EMPTY`, but someone is waiting for it, so the
+/// writer has to notify the waiters, see `WaitQueue`.
+const WAITING: u8 = 3;
+
+/// Where the entries are stored.
+///
+/// The values and the states are stored in 2 separate arrays so that the
+/// values are contiguous, see `FixSizedVec::as_slice()`.
+///
+/// The `AtomicU8` states are used to indicate whether the value at the same
+/// index has been initialized or not, we cannot use `[Option<T>; N]` here as
+/// updating a `Option` is not atomic, when reading it with the `get()` method,
+/// partially initialized memory can be read and causes UB.
+///
+/// There is indeed an `AtomicOption` crate, but it is basically equivalent
+/// to using an atomic flag, and we need more states, e.g., `ABANDONED`, anyway.
+enum Storage<T, const N: usize> {
+    /// Stored inside the vector, created by `new()`.
+    Inline {
+        values: [Value<T>; N],
+        states: [AtomicU8; N],
+    },
+    /// Stored on the heap, created by `with_capacity()` or
+    /// `waitable_with_capacity()`.
+    Heap {
+        values: Box<[Value<T>]>,
+        states: Box<[AtomicU8]>,
+    },
+}
+
+impl<T> Storage<T, 0> {
+    /// Allocate `capacity` empty entries on the heap.
+    fn heap(capacity: usize) -> Self {
+        let values = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        let states = (0..capacity).map(|_| AtomicU8::new(EMPTY)).collect();
+
+        Storage::Heap { values, states }
+    }
+}
+
+impl<T, const N: usize> Storage<T, N> {
+    /// Return the values.
+    fn values(&self) -> &[Value<T>] {
+        match self {
+            Storage::Inline { values, .. } => values,
+            Storage::Heap { values, .. } => values,
+        }
+    }
+
+    /// Return the states.
+    fn states(&self) -> &[AtomicU8] {
+        match self {
+            Storage::Inline { states, .. } => states,
+            Storage::Heap { states, .. } => states,
+        }
+    }
+
+    /// Return the values and states with exclusive access.
+    fn as_mut(&mut self) -> (&mut [Value<T>], &mut [AtomicU8]) {
+        match self {
+            Storage::Inline { values, states } => (values, states),
+            Storage::Heap { values, states } => (values, states),
+        }
+    }
 }
 
-impl<T: Debug, const N: usize> Debug for FixSizedVec<T, N> {
+impl<T: Debug, const N: usize, const WAIT: bool> Debug
+    for FixSizedVec<T, N, WAIT>
+{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut array = StdVec::with_capacity(N);
-        // SAFETY:
-        // We only read the array, and we won't read a piece of memory what is
-        // in intermediate state as guarded by that `AtomicBool` flag.
-        for (val, inited) in unsafe { &*self.array.get() } {
-            if inited.load(Ordering::Relaxed) {
-                // SAFETY:
-                // It is guaranteed to be initialized as the `inited` flag is true.
-                array.push(Some(unsafe { val.assume_init_ref() }));
-            } else {
-                array.push(None);
-            }
+        let mut array = StdVec::with_capacity(self.capacity());
+        for idx in 0..self.capacity() {
+            array.push(self.get(idx));
         }
 
         f.debug_struct("FixSizedVec")
             .field("array", &array)
-            .field("len", &self.len)
+            .field("len", &self.len())
+            .field("committed", &self.committed_len())
             .finish()
     }
 }
 
+impl<T, const N: usize, const WAIT: bool> Drop for FixSizedVec<T, N, WAIT> {
+    fn drop(&mut self) {
+        // When dropping, it is guaranteed that no one is accessing the vector,
+        // so we can read the states without any synchronization, only the
+        // initialized entries are dropped.
+        let (values, states) = self.array.as_mut();
+        for (val, state) in values.iter_mut().zip(states) {
+            if *state.get_mut() == READY {
+                // SAFETY:
+                // It is guaranteed to be initialized as the state is `READY`,
+                // and it is dropped only once as the vector is going away.
+                unsafe { val.get_mut().assume_init_drop() };
+            }
+        }
+    }
+}
+
 // SAFETY:
 //
-// It is synchronized:
+// With a `&FixSizedVec<T, N>`, other threads can push values into it, which
+// moves `T`s across threads, and get `&T`s out of it, which shares `T`s across
+// threads, so `T` has to be both `Send` and `Sync`.
+//
+// `Send` is automatically implemented when `T: Send` as the vector owns the
+// entries, no matter where they are stored.
+//
+// And it is synchronized:
 //
 // * For write:
 //
@@ -58,139 +211,1916 @@ impl<T: Debug, const N: usize> Debug for FixSizedVec<T, N> {
 // * For read:
 //
 //   1. Even though the value stored in `MaybeUninit<T>` can be partially initialized,
-//      we won't read it cause we will check the `AtomicBool` flag first before we
+//      we won't read it cause we will check the `AtomicU8` state first before we
 //      access the value.
 //
-//      It can be seen as `AtomicBool<MaybeUninit<T>>`
+//      It can be seen as `AtomicU8<MaybeUninit<T>>`, the state is set to
+//      `READY` with `Release` after the value is written, and checked with
+//      `Acquire` before the value is read, so a reader that sees `READY` also
+//      sees the whole value.
 //
 //   2. A written value won't be changed so that we can safely read an item.
-unsafe impl<T, const N: usize> Sync for FixSizedVec<T, N> {}
+unsafe impl<T: Send + Sync, const N: usize, const WAIT: bool> Sync
+    for FixSizedVec<T, N, WAIT>
+{
+}
 
-impl<T, const N: usize> FixSizedVec<T, N> {
-    /// Create an empty vector.
-    pub fn new() -> Self {
-        let array = std::array::from_fn(|_| {
-            (MaybeUninit::uninit(), AtomicBool::new(false))
-        });
+impl<T> FixSizedVec<T> {
+    /// Create an empty vector that can hold `capacity` items.
+    ///
+    /// The entries are allocated on the heap directly, so it won't overflow
+    /// the stack no matter how large `capacity` is.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_storage(Storage::heap(capacity))
+    }
+}
+
+impl<T> FixSizedVec<T, 0, true> {
+    /// Same as [`FixSizedVec::with_capacity()`], but the vector can be waited
+    /// on, see [`FixSizedVec#waiting`].
+    pub fn waitable_with_capacity(capacity: usize) -> Self {
+        Self::with_storage(Storage::heap(capacity))
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> FixSizedVec<T, N, WAIT> {
+    /// Create an empty vector that can hold `N` items.
+    ///
+    /// It is a `const fn`, so the vector can be put in a `static` item:
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// static TABLE: FixSizedVec<&str, 4096> = FixSizedVec::new();
+    ///
+    /// let idx = TABLE.push("entry").unwrap();
+    /// assert_eq!(TABLE.get(idx), Some(&"entry"));
+    /// ```
+    pub const fn new() -> Self {
+        Self::with_storage(Storage::Inline {
+            values: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            states: [const { AtomicU8::new(EMPTY) }; N],
+        })
+    }
+
+    const fn with_storage(array: Storage<T, N>) -> Self {
         Self {
-            array: UnsafeCell::new(array),
+            array,
             len: AtomicUsize::new(0),
+            committed: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Return the maximum number of items it can hold.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        match &self.array {
+            Storage::Inline { .. } => N,
+            Storage::Heap { values, .. } => values.len(),
         }
     }
 
     /// Push an item to it
     ///
-    /// Return `Ok(())` when it is successfully written, `Err(())` when the vector is full.
-    #[allow(clippy::result_unit_err)] // using unit as the err type is ok for demo code
-    pub fn push(&self, val: T) -> Result<(), ()> {
+    /// Return `Ok(index)` when it is successfully written to `index`, or
+    /// [`PushError`] that carries `val` back when the vector is full.
+    pub fn push(&self, val: T) -> Result<usize, PushError<T>> {
+        self.push_get(val).map(|(idx, _)| idx)
+    }
+
+    /// Same as [`FixSizedVec::push()`], but also return a reference to the
+    /// stored item.
+    ///
+    /// # Wait-freedom
+    ///
+    /// The index is reserved with a single `fetch_add()`, so every push
+    /// finishes in a bounded number of steps no matter how many threads are
+    /// pushing, unlike a CAS loop where a thread can keep losing the race.
+    ///
+    /// When the vector is full, the reserved index is out of bound, `len` then
+    /// gets clamped back to the capacity so that it won't overflow no matter
+    /// how many failed pushes there are.
+    pub fn push_get(&self, val: T) -> Result<(usize, &T), PushError<T>> {
+        let Some(idx) = self.reserve_one() else {
+            return Err(PushError(val));
+        };
+
+        // SAFETY:
+        // `idx` is only handed to this thread by `reserve_one()`.
+        let val = unsafe { self.write(idx, val) };
+
+        Ok((idx, val))
+    }
+
+    /// Push the item returned by `f`, which is written to the vector directly.
+    ///
+    /// An index is reserved before `f` is called, so for large items, the
+    /// compiler gets the chance to build it in the entry, rather than on the
+    /// stack and then copy it in. Return [`PushError`] that carries `f` back,
+    /// without calling it, when the vector is full.
+    ///
+    /// If `f` panics, the reserved index gets abandoned, see
+    /// [`FixSizedVec::is_abandoned()`].
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// let vec = FixSizedVec::<[u8; 4096], 4>::new();
+    /// let idx = vec.push_with(|| [1; 4096]).unwrap();
+    /// assert_eq!(vec.get(idx).unwrap()[4095], 1);
+    /// ```
+    pub fn push_with<F>(&self, f: F) -> Result<usize, PushError<F>>
+    where
+        F: FnOnce() -> T,
+    {
+        let Some(idx) = self.reserve_one() else {
+            return Err(PushError(f));
+        };
+
+        // SAFETY:
+        // `idx` is only handed to this thread by `reserve_one()`.
+        unsafe { self.write_with(idx, f) };
+
+        Ok(idx)
+    }
+
+    /// Push an item only if the length is `expected`, so that it is written
+    /// to index `expected`.
+    ///
+    /// Return [`PushIfLenError::Conflict`] that carries the current length
+    /// and `val` back if the length is not `expected`, or
+    /// [`PushIfLenError::Full`] if it is, but the vector is full.
+    ///
+    /// ```
+    /// use demystify_boxcar::{error::PushIfLenError, fix_sized::FixSizedVec};
+    ///
+    /// let vec = FixSizedVec::<&str, 1>::new();
+    /// assert_eq!(vec.push_if_len(0, "a"), Ok(0));
+    /// assert_eq!(
+    ///     vec.push_if_len(0, "b"),
+    ///     Err(PushIfLenError::Conflict { len: 1, value: "b" })
+    /// );
+    /// assert_eq!(vec.push_if_len(1, "b"), Err(PushIfLenError::Full("b")));
+    /// ```
+    pub fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        let capacity = self.capacity();
+        if expected >= capacity {
+            let len = self.len();
+            return Err(if len == expected {
+                PushIfLenError::Full(val)
+            } else {
+                PushIfLenError::Conflict { len, value: val }
+            });
+        }
+
+        if let Err(len) = self.len.compare_exchange(
+            expected,
+            expected + 1,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            // It can exceed the capacity, see `len()`.
+            let len = len.min(capacity);
+            return Err(PushIfLenError::Conflict { len, value: val });
+        }
+
+        // SAFETY:
+        // `expected` is only handed to this thread by the CAS.
+        unsafe { self.write(expected, val) };
+
+        Ok(expected)
+    }
+
+    /// Reserve an index for a push, return `None` if the vector is full.
+    ///
+    /// The index is reserved with a single `fetch_add()`, see
+    /// [`FixSizedVec::push_get()`].
+    fn reserve_one(&self) -> Option<usize> {
+        let capacity = self.capacity();
+        let idx = self.len.fetch_add(1, Ordering::Relaxed);
+        if idx >= capacity {
+            // All the indexes below `capacity` have been reserved, so any value
+            // that is not smaller than `capacity` means the same thing.
+            self.len.fetch_min(capacity, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(idx)
+    }
+
+    /// Reserve `n` contiguous indexes at once, return `None` if there are fewer
+    /// than `n` indexes left.
+    ///
+    /// The indexes are decided right away, but the values can be written
+    /// later, possibly on another thread, through the returned
+    /// [`SlotReservation`].
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// let vec = FixSizedVec::<i32, 8>::new();
+    /// let mut reservation = vec.reserve(2).unwrap();
+    /// assert_eq!(vec.push(3), Ok(2));
+    ///
+    /// std::thread::scope(|s| {
+    ///     s.spawn(move || {
+    ///         assert_eq!(reservation.write(1), Ok(0));
+    ///         assert_eq!(reservation.write(2), Ok(1));
+    ///     });
+    /// });
+    /// assert_eq!(vec.as_slice(), [1, 2, 3]);
+    /// ```
+    pub fn reserve(&self, n: usize) -> Option<SlotReservation<'_, T, N, WAIT>> {
+        let capacity = self.capacity();
+
+        // Unlike `push()`, a CAS loop is used here, so that either all the `n`
+        // indexes are reserved, or none of them is.
         loop {
-            let snapshot = self.len.load(Ordering::Relaxed);
-            if snapshot == N {
-                return Err(());
+            let start = self.len.load(Ordering::Relaxed);
+            let end = start.checked_add(n)?;
+            if end > capacity {
+                return None;
             }
 
             if self
                 .len
                 .compare_exchange(
-                    snapshot,
-                    snapshot + 1,
+                    start,
+                    end,
                     Ordering::Relaxed,
                     Ordering::Relaxed,
                 )
                 .is_ok()
             {
-                let ptr = self.array.get();
-
-                // SAFETY:
-                //
-                // It is safe because
-                //     1. the raw pointer `ptr` comes from `&self` so that it
-                //        cannot be NULL or dangling.
-                //     2. `snapshot` is guaranteed to be valid index as it has
-                //        already been checked.
-                let (entry, inited) =
-                    unsafe { (*ptr).get_unchecked_mut(snapshot) };
-
-                assert!(!inited.load(Ordering::Relaxed));
-                entry.write(val);
-                inited.store(true, Ordering::Relaxed);
-
-                return Ok(());
+                return Some(SlotReservation {
+                    vec: self,
+                    next: start,
+                    end,
+                });
             }
         }
     }
 
+    /// Push all the items of `iter`, return the indexes they are written to.
+    ///
+    /// The indexes are reserved at once with [`FixSizedVec::reserve()`], so
+    /// the items are stored contiguously, in order. Return [`PushError`] that
+    /// carries `iter` back, untouched, if there are not enough indexes left.
+    ///
+    /// # Panic Safety
+    ///
+    /// If `iter` panics, or yields fewer items than its `len()`, the reserved
+    /// indexes that are not written get abandoned, see
+    /// [`FixSizedVec::is_abandoned()`]. The items written before that stay in
+    /// the vector and are dropped along with it.
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// let vec = FixSizedVec::<i32, 4>::new();
+    /// assert_eq!(vec.push_many([1, 2, 3]).ok(), Some(0..3));
+    /// assert!(vec.push_many([4, 5]).is_err());
+    /// assert_eq!(vec.as_slice(), [1, 2, 3]);
+    /// ```
+    pub fn push_many<I>(
+        &self,
+        iter: I,
+    ) -> Result<Range<usize>, PushError<I::IntoIter>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let mut iter = iter.into_iter();
+        let Some(mut reservation) = self.reserve(iter.len()) else {
+            return Err(PushError(iter));
+        };
+
+        let start = reservation.remaining().start;
+        // `take()` as `len()` is not trusted, the extra items are not consumed.
+        for val in iter.by_ref().take(reservation.remaining().len()) {
+            // It won't fail as at most `remaining().len()` items are written.
+            let _ = reservation.write(val);
+        }
+
+        Ok(start..reservation.remaining().start)
+    }
+
     /// Get the value at `idx`
     ///
     /// For uninitialized value, a `None` is returned. Otherwise, return an
-    /// reference to the value.
+    /// reference to the value. Use [`FixSizedVec::slot_state()`] to tell why
+    /// the value is not there.
     ///
     // We don't need to worry that the value will be modified while holding
     // the returned reference as the stored value won't be modified at all.
     pub fn get(&self, idx: usize) -> Option<&T> {
-        if idx >= N {
+        if idx >= self.capacity() {
             return None;
         }
 
-        let p = self.array.get();
-
-        // SAFETY:
-        // The pointer `p` cannot be dangling or NULL as it comes from `&self`
-        let (val, inited) = &unsafe { &*p }[idx];
+        let (val, state) = self.slot(idx);
 
-        if inited.load(Ordering::Relaxed) {
+        // This `Acquire` load synchronizes with the `Release` store in `push()`,
+        // everything the pushing thread wrote before setting the state (i.e.,
+        // the value itself, including the heap memory it owns) is visible to
+        // us once we see `READY`.
+        if state.load(Ordering::Acquire) == READY {
             // SAFETY:
-            // It is guaranteed to be initialized as the `inited` flag is true.
-            Some(unsafe { val.assume_init_ref() })
+            // It is guaranteed to be initialized as the state is `READY`,
+            // and it won't be written again.
+            Some(unsafe { (*val.get()).assume_init_ref() })
         } else {
             None
         }
     }
 
-    /// Return the length.
+    /// Return the length, i.e., the number of reserved indexes, which is never
+    /// greater than the capacity.
     ///
     /// It is inaccurate due to concurrent appends.
     #[inline]
     pub fn len(&self) -> usize {
-        self.len.load(Ordering::Relaxed)
+        self.len.load(Ordering::Relaxed).min(self.capacity())
+    }
+
+    /// Get a mutable reference to the value at `idx`, `None` if it is not
+    /// written.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let (values, states) = self.array.as_mut();
+        if idx >= values.len() || *states[idx].get_mut() != READY {
+            return None;
+        }
+
+        // SAFETY:
+        // It is guaranteed to be initialized as the state is `READY`.
+        Some(unsafe { values[idx].get_mut().assume_init_mut() })
+    }
+
+    /// Iterate over the written items, with mutable references.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let len = self.len();
+        let (values, states) = self.array.as_mut();
+
+        IterMut {
+            inner: values[..len].iter_mut().zip(states[..len].iter_mut()),
+        }
+    }
+
+    /// Shorten the vector to `len` items, the written items at or after `len`
+    /// are dropped.
+    ///
+    /// It has no effect if `len` is not smaller than the current length. The
+    /// states are reset so the vector can be pushed to again after this, the
+    /// first pushed item is written to index `len`.
+    pub fn truncate(&mut self, len: usize) {
+        let old_len = self.len();
+        if len >= old_len {
+            return;
+        }
+
+        let (values, states) = self.array.as_mut();
+        for idx in len..old_len {
+            let state = states[idx].get_mut();
+            if *state == READY {
+                // SAFETY:
+                // It is guaranteed to be initialized as the state is `READY`,
+                // and it is dropped only once as the state is reset.
+                unsafe { values[idx].get_mut().assume_init_drop() };
+            }
+            // Abandoned indexes can be used again as well.
+            *state = EMPTY;
+        }
+
+        *self.len.get_mut() = len;
+        let committed = self.committed.get_mut();
+        *committed = (*committed).min(len);
+    }
+
+    /// Remove all the items.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Move the written items into a `std::vec::Vec`, in the order of their
+    /// indexes, without cloning them.
+    pub fn into_vec(self) -> StdVec<T> {
+        self.into_iter().collect()
+    }
+
+    /// Iterate over the written items.
+    ///
+    /// The length is taken when it gets created, items pushed after that won't
+    /// be visited, and indexes that are reserved but not written yet are
+    /// skipped, so each written item within the length is visited once.
+    pub fn iter(&self) -> Iter<'_, Self> {
+        Iter::new(self)
+    }
+
+    /// Return a cursor that starts from index 0, and follows the items as they
+    /// are written.
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// let vec = FixSizedVec::<i32, 4>::new();
+    /// let mut cursor = vec.cursor();
+    /// let mut reservation = vec.reserve(1).unwrap();
+    /// vec.push(2).unwrap();
+    /// // Index 0 is not written yet.
+    /// assert_eq!(cursor.try_next(), None);
+    /// reservation.write(1).unwrap();
+    /// assert_eq!(cursor.try_next(), Some(&1));
+    /// assert_eq!(cursor.try_next(), Some(&2));
+    /// ```
+    pub fn cursor(&self) -> Cursor<'_, T, N, WAIT> {
+        Cursor {
+            vec: self,
+            position: 0,
+        }
+    }
+
+    /// Return the state of the entry at `idx`.
+    ///
+    /// Unlike `get()`, which returns `None` whenever the value is not
+    /// readable, it tells whether the value is going to be written, so the
+    /// caller can decide to wait for it or give up.
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::{FixSizedVec, SlotState};
+    ///
+    /// let vec = FixSizedVec::<i32, 4>::new();
+    /// let reservation = vec.reserve(1).unwrap();
+    /// vec.push(1).unwrap();
+    /// assert_eq!(vec.slot_state(0), SlotState::Pending);
+    /// assert_eq!(vec.slot_state(1), SlotState::Ready(&1));
+    /// assert_eq!(vec.slot_state(2), SlotState::Empty);
+    /// assert_eq!(vec.slot_state(4), SlotState::OutOfBounds);
+    ///
+    /// drop(reservation);
+    /// assert_eq!(vec.slot_state(0), SlotState::Abandoned);
+    /// ```
+    pub fn slot_state(&self, idx: usize) -> SlotState<'_, T> {
+        if idx >= self.capacity() {
+            return SlotState::OutOfBounds;
+        }
+
+        let (val, state) = self.slot(idx);
+        // Same as `get()`, this `Acquire` load pairs with the `Release` store
+        // in `write()`.
+        match state.load(Ordering::Acquire) {
+            READY => {
+                // SAFETY:
+                // It is guaranteed to be initialized as the state is `READY`,
+                // and it won't be written again.
+                SlotState::Ready(unsafe { (*val.get()).assume_init_ref() })
+            }
+            ABANDONED => SlotState::Abandoned,
+            // Loaded after the state, an index that was not reserved when the
+            // state is loaded can be reserved since, but then it is `Pending`
+            // anyway.
+            _ if idx < self.len() => SlotState::Pending,
+            _ => SlotState::Empty,
+        }
+    }
+
+    /// Return `true` if `idx` has been reserved but will never be written, as
+    /// the [`SlotReservation`] it belongs to was dropped before writing it.
+    ///
+    /// Unlike an index that is being written, such a hole is permanent, until
+    /// it is removed by `truncate()` or `clear()`.
+    pub fn is_abandoned(&self, idx: usize) -> bool {
+        idx < self.capacity()
+            && self.slot(idx).1.load(Ordering::Relaxed) == ABANDONED
+    }
+
+    /// Return the length of the committed prefix, i.e., the largest `n` such
+    /// that all the entries in `0..n` are initialized.
+    ///
+    /// Unlike `len()`, which counts reserved indexes, it only moves forward
+    /// past an index once every index before it is written, so it never
+    /// exceeds `len()`. It stops at the first abandoned index for good, see
+    /// [`FixSizedVec::is_abandoned()`].
+    ///
+    /// # Cost
+    ///
+    /// The prefix is computed lazily, only the readers that ask for it pay:
+    /// it checks the entries after the last seen prefix, and records how far
+    /// it gets for the next call, so each entry is checked about once.
+    pub fn committed_len(&self) -> usize {
+        let capacity = self.capacity();
+        let start = self.committed.load(Ordering::Acquire);
+
+        let mut end = start;
+        // This `Acquire` load pairs with the `Release` store in `write()`, so
+        // the values below `end` are visible to us.
+        while end < capacity
+            && self.slot(end).1.load(Ordering::Acquire) == READY
+        {
+            end += 1;
+        }
+        if end == start {
+            return start;
+        }
+
+        // `Release` so that a reader that loads it sees the values below it,
+        // `Acquire` for the same reason in case another reader got further.
+        self.committed.fetch_max(end, Ordering::AcqRel).max(end)
+    }
+
+    /// Get the value at `idx` with ordered visibility.
+    ///
+    /// Same as `get()`, except that it only returns `Some` for indexes within
+    /// the committed prefix, so once it returns `Some`, all the lower indexes
+    /// are readable as well, even if the value at `idx` has been written
+    /// earlier than some of them.
+    pub fn get_committed(&self, idx: usize) -> Option<&T> {
+        if idx < self.committed_len() {
+            self.get(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Return the committed prefix as a slice.
+    ///
+    /// All the entries within the committed prefix are initialized and won't
+    /// be changed, so they can be borrowed as `&[T]` directly without copying.
+    /// Items pushed after it is returned are not included, call it again to
+    /// see them.
+    pub fn as_slice(&self) -> &[T] {
+        let committed = self.committed_len();
+        let values = self.array.values();
+
+        // SAFETY:
+        // * `Value<T>` has the same memory layout as `T`, as both `UnsafeCell`
+        //   and `MaybeUninit` are `#[repr(transparent)]`.
+        // * The first `committed` values are initialized, and the `Acquire`
+        //   loads in `committed_len()` make them visible to us.
+        // * Initialized values won't be written again, so they can be shared
+        //   for as long as `&self` lives.
+        unsafe {
+            std::slice::from_raw_parts(values.as_ptr() as *const T, committed)
+        }
+    }
+
+    /// Write `val` to `idx` and publish it.
+    ///
+    /// # Safety
+    ///
+    /// `idx` has to be reserved by the caller, and not written yet.
+    unsafe fn write(&self, idx: usize, val: T) -> &T {
+        // SAFETY:
+        // The caller guarantees the same requirements.
+        unsafe { self.write_with(idx, || val) }
+    }
+
+    /// Write the value returned by `f` to `idx` and publish it, `idx` gets
+    /// abandoned if `f` panics.
+    ///
+    /// # Safety
+    ///
+    /// Same as `write()`.
+    unsafe fn write_with<F: FnOnce() -> T>(&self, idx: usize, f: F) -> &T {
+        /// Abandon the index when `f` panics.
+        struct Guard<'a, T, const N: usize, const WAIT: bool>(
+            &'a FixSizedVec<T, N, WAIT>,
+            usize,
+        );
+
+        impl<T, const N: usize, const WAIT: bool> Drop for Guard<'_, T, N, WAIT> {
+            fn drop(&mut self) {
+                self.0.abandon(self.1);
+            }
+        }
+
+        let (entry, state) = self.slot(idx);
+        assert!(matches!(state.load(Ordering::Relaxed), EMPTY | WAITING));
+
+        let guard = Guard(self, idx);
+        // SAFETY:
+        // The caller has reserved `idx`, and readers won't touch the value
+        // until the state is `READY`, so we have exclusive access to it.
+        let val = unsafe { (*entry.get()).write(f()) };
+        std::mem::forget(guard);
+        // Publish the value, pairs with the `Acquire` load in `get()`.
+        self.publish(state, READY, Ordering::Release);
+
+        val
+    }
+
+    /// Mark `idx` as abandoned, it has to be reserved but not written.
+    fn abandon(&self, idx: usize) {
+        let (_, state) = self.slot(idx);
+        self.publish(state, ABANDONED, Ordering::Relaxed);
+    }
+
+    /// Set the state of a reserved entry to `READY` or `ABANDONED`.
+    ///
+    /// With `WAIT`, it notifies the waiters if the entry is `WAITING`, see
+    /// `WaitQueue`, otherwise no one can be waiting, a plain store will do.
+    #[inline]
+    fn publish(&self, state: &AtomicU8, new: u8, order: Ordering) {
+        if !WAIT {
+            state.store(new, order);
+        } else if state.swap(new, order) == WAITING {
+            self.waiters.notify();
+        }
+    }
+
+    /// Mark the entry at `idx` as `WAITING` if it is not written yet, so that
+    /// the writer notifies the waiters, and return its state.
+    ///
+    /// `idx` has to be smaller than the capacity, and `WAIT` has to be `true`,
+    /// otherwise the writer won't notify.
+    fn watch(&self, idx: usize) -> u8 {
+        let (_, state) = self.slot(idx);
+        // `Acquire` for the same reason as in `get()`, in case it is `READY`.
+        match state.compare_exchange(
+            EMPTY,
+            WAITING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => WAITING,
+            Err(state) => state,
+        }
+    }
+
+    /// Return the value and the state of the entry at `idx`.
+    ///
+    /// `idx` has to be smaller than the capacity.
+    fn slot(&self, idx: usize) -> (&Value<T>, &AtomicU8) {
+        (&self.array.values()[idx], &self.array.states()[idx])
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{sync::Arc, thread::spawn};
+/// Waiting for the values to be written, see [`FixSizedVec#waiting`].
+impl<T, const N: usize> FixSizedVec<T, N, true> {
+    /// Block until the value at `idx` is written, and return it.
+    ///
+    /// Return `None` if it will never be written, i.e., `idx` is out of bound
+    /// or abandoned.
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    ///
+    /// let vec = FixSizedVec::<i32, 4, true>::new();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| vec.push(1));
+    ///     assert_eq!(vec.wait_for(0), Some(&1));
+    /// });
+    /// ```
+    pub fn wait_for(&self, idx: usize) -> Option<&T> {
+        self.wait_for_until(idx, None)
+    }
 
-    #[test]
-    fn it_works() {
-        let vec = Arc::new(FixSizedVec::<i32, 25>::new());
-        let mut handles = Vec::new();
+    /// Same as [`FixSizedVec::wait_for()`], but also return `None` when
+    /// `timeout` elapses.
+    pub fn wait_for_timeout(
+        &self,
+        idx: usize,
+        timeout: Duration,
+    ) -> Option<&T> {
+        // A deadline too far away to be represented is as good as none.
+        self.wait_for_until(idx, Instant::now().checked_add(timeout))
+    }
 
-        for thread_id in 0..5 {
-            handles.push(spawn({
-                let vec = Arc::clone(&vec);
+    /// Return a future that resolves to the value at `idx` once it is
+    /// written.
+    ///
+    /// Same as [`FixSizedVec::wait_for()`], it resolves to `None` if the value
+    /// will never be written. It works with any executor, the task is woken up
+    /// by the push that writes the value.
+    ///
+    /// ```
+    /// use demystify_boxcar::fix_sized::FixSizedVec;
+    /// # fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    /// #     let mut fut = std::pin::pin!(fut);
+    /// #     let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    /// #     loop {
+    /// #         if let std::task::Poll::Ready(ret) = fut.as_mut().poll(&mut cx) {
+    /// #             return ret;
+    /// #         }
+    /// #     }
+    /// # }
+    ///
+    /// let vec = FixSizedVec::<i32, 4, true>::new();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| vec.push(1));
+    ///     assert_eq!(block_on(vec.wait_for_async(0)), Some(&1));
+    /// });
+    /// ```
+    pub fn wait_for_async(&self, idx: usize) -> WaitFor<'_, T, N> {
+        WaitFor {
+            vec: self,
+            idx,
+            waker: WakerSlot::new(&self.waiters),
+        }
+    }
 
-                move || {
-                    for _ in 0..5 {
-                        vec.push(thread_id).unwrap();
+    fn wait_for_until(
+        &self,
+        idx: usize,
+        deadline: Option<Instant>,
+    ) -> Option<&T> {
+        self.waiters
+            .wait_until(deadline, || self.check_written(idx))
+            .flatten()
+    }
+
+    /// Return `None` if the value at `idx` may still be written, otherwise
+    /// return whether it is written.
+    fn check_written(&self, idx: usize) -> Option<Option<&T>> {
+        loop {
+            match self.slot_state(idx) {
+                SlotState::Ready(val) => return Some(Some(val)),
+                SlotState::Abandoned | SlotState::OutOfBounds => {
+                    return Some(None)
+                }
+                SlotState::Empty | SlotState::Pending => {
+                    if self.watch(idx) == WAITING {
+                        return None;
                     }
                 }
-            }));
+            }
         }
+    }
 
-        for handle in handles {
-            handle.join().unwrap();
-        }
+    /// Block until the committed prefix has at least `n` items, and return
+    /// its length.
+    ///
+    /// Return `None` if it will never get there, i.e., `n` is greater than
+    /// the capacity, or the committed prefix is stuck at an abandoned index.
+    pub fn wait_len_at_least(&self, n: usize) -> Option<usize> {
+        self.wait_len_at_least_until(n, None)
+    }
 
-        assert_eq!(vec.len(), 25);
-        let mut counter = [0_usize; 5];
-        for idx in 0..25 {
-            let num = *vec.get(idx).unwrap();
-            counter[num as usize] += 1;
-        }
+    /// Same as [`FixSizedVec::wait_len_at_least()`], but also return `None`
+    /// when `timeout` elapses.
+    pub fn wait_len_at_least_timeout(
+        &self,
+        n: usize,
+        timeout: Duration,
+    ) -> Option<usize> {
+        self.wait_len_at_least_until(n, Instant::now().checked_add(timeout))
+    }
 
-        for item in counter {
-            assert_eq!(item, 5);
+    fn wait_len_at_least_until(
+        &self,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Option<usize> {
+        if n > self.capacity() {
+            return None;
         }
+
+        self.waiters
+            .wait_until(deadline, || loop {
+                let committed = self.committed_len();
+                if committed >= n {
+                    return Some(Some(committed));
+                }
+                if self.is_abandoned(committed) {
+                    return Some(None);
+                }
+                // `committed < n <= capacity`, wait for the entry the prefix
+                // stops at, check again if it is written or abandoned since.
+                if self.watch(committed) == WAITING {
+                    return None;
+                }
+            })
+            .flatten()
+    }
+}
+
+impl<'a, T, const N: usize, const WAIT: bool> IntoIterator
+    for &'a FixSizedVec<T, N, WAIT>
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, FixSizedVec<T, N, WAIT>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> IntoIterator
+    for FixSizedVec<T, N, WAIT>
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, N, WAIT>;
+
+    /// Move the written items out of the vector, in the order of their
+    /// indexes.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { vec: self, idx: 0 }
+    }
+}
+
+/// State of an entry, returned by [`FixSizedVec::slot_state()`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum SlotState<'a, T> {
+    /// The index is not reserved yet.
+    Empty,
+    /// The index is reserved, and the value is being written.
+    Pending,
+    /// The value is written.
+    Ready(&'a T),
+    /// The index is reserved, but the value will never be written, see
+    /// [`FixSizedVec::is_abandoned()`].
+    Abandoned,
+    /// The index is not smaller than the capacity, it will never be written.
+    OutOfBounds,
+}
+
+impl<T> Clone for SlotState<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SlotState<'_, T> {}
+
+/// A cursor that yields the items in the order of their indexes, including
+/// the ones written after it is created, returned by [`FixSizedVec::cursor()`].
+///
+/// It stops at the first index that is not written yet, even if some later
+/// ones are, and skips the abandoned indexes.
+pub struct Cursor<'a, T, const N: usize, const WAIT: bool = false> {
+    vec: &'a FixSizedVec<T, N, WAIT>,
+    /// Index of the item to visit next.
+    position: usize,
+}
+
+impl<'a, T, const N: usize, const WAIT: bool> Cursor<'a, T, N, WAIT> {
+    /// Return the index of the item to visit next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Return the next item, or `None` if it is not written yet, or the end
+    /// of the vector is reached.
+    pub fn try_next(&mut self) -> Option<&'a T> {
+        self.poll_next(false).flatten()
+    }
+
+    /// Return `None` if the next item may still be written, otherwise
+    /// return the next item, `None` if the end is reached.
+    ///
+    /// With `watch`, the entry is marked as `WAITING` before returning `None`,
+    /// see `FixSizedVec::watch()`.
+    fn poll_next(&mut self, watch: bool) -> Option<Option<&'a T>> {
+        loop {
+            match self.vec.slot_state(self.position) {
+                SlotState::Ready(val) => {
+                    self.position += 1;
+                    return Some(Some(val));
+                }
+                SlotState::Abandoned => self.position += 1,
+                SlotState::OutOfBounds => return Some(None),
+                SlotState::Empty | SlotState::Pending => {
+                    if !watch || self.vec.watch(self.position) == WAITING {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Cursor<'a, T, N, true> {
+    /// Block until the next item is written, and return it.
+    ///
+    /// Return `None` if the end of the vector is reached.
+    pub fn next_blocking(&mut self) -> Option<&'a T> {
+        self.vec
+            .waiters
+            .wait_until(None, || self.poll_next(true))
+            .flatten()
+    }
+
+    /// Same as [`Cursor::next_blocking()`], but also return `None` when
+    /// `timeout` elapses.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<&'a T> {
+        // Same as `wait_for_timeout()`, no deadline if it is too far away.
+        let deadline = Instant::now().checked_add(timeout);
+        self.vec
+            .waiters
+            .wait_until(deadline, || self.poll_next(true))
+            .flatten()
+    }
+
+    /// Return a future that resolves to the next item once it is written, or
+    /// `None` if the end of the vector is reached.
+    pub fn next_async(&mut self) -> Next<'_, 'a, T, N> {
+        Next {
+            waker: WakerSlot::new(&self.vec.waiters),
+            cursor: self,
+        }
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> Debug for Cursor<'_, T, N, WAIT> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cursor")
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+/// Future returned by [`Cursor::next_async()`].
+pub struct Next<'c, 'a, T, const N: usize> {
+    cursor: &'c mut Cursor<'a, T, N, true>,
+    waker: WakerSlot<'a>,
+}
+
+impl<'a, T, const N: usize> Future for Next<'_, 'a, T, N> {
+    type Output = Option<&'a T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let cursor = &mut *this.cursor;
+        this.waker.poll_until(cx, || cursor.poll_next(true))
+    }
+}
+
+impl<T, const N: usize> Debug for Next<'_, '_, T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Next")
+            .field("cursor", &self.cursor)
+            .finish()
+    }
+}
+
+/// Future returned by [`FixSizedVec::wait_for_async()`].
+pub struct WaitFor<'a, T, const N: usize> {
+    vec: &'a FixSizedVec<T, N, true>,
+    idx: usize,
+    waker: WakerSlot<'a>,
+}
+
+impl<'a, T, const N: usize> Future for WaitFor<'a, T, N> {
+    type Output = Option<&'a T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (vec, idx) = (this.vec, this.idx);
+        this.waker.poll_until(cx, || vec.check_written(idx))
+    }
+}
+
+impl<T, const N: usize> Debug for WaitFor<'_, T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WaitFor").field("idx", &self.idx).finish()
+    }
+}
+
+/// A contiguous range of indexes reserved by [`FixSizedVec::reserve()`].
+///
+/// The reserved indexes are written in order with
+/// [`SlotReservation::write()`], each of them is published as soon as it is
+/// written. Indexes that are not written when it is dropped, e.g., when the
+/// producer panics, are marked as abandoned, see
+/// [`FixSizedVec::is_abandoned()`].
+///
+/// It can be sent to another thread as long as the vector can be shared.
+pub struct SlotReservation<'a, T, const N: usize, const WAIT: bool = false> {
+    vec: &'a FixSizedVec<T, N, WAIT>,
+    /// The next index to write.
+    next: usize,
+    /// End of the reserved range, exclusive.
+    end: usize,
+}
+
+impl<'a, T, const N: usize, const WAIT: bool> SlotReservation<'a, T, N, WAIT> {
+    /// Return the indexes that are not written yet.
+    pub fn remaining(&self) -> Range<usize> {
+        self.next..self.end
+    }
+
+    /// Write `val` to the next reserved index, return that index.
+    ///
+    /// Return [`PushError`] that carries `val` back if all the reserved indexes
+    /// have been written.
+    pub fn write(&mut self, val: T) -> Result<usize, PushError<T>> {
+        if self.next == self.end {
+            return Err(PushError(val));
+        }
+
+        let idx = self.next;
+        self.next += 1;
+        // SAFETY:
+        // `idx` is within the reserved range, and it is written only once as
+        // `next` has been moved forward.
+        unsafe { self.vec.write(idx, val) };
+
+        Ok(idx)
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> Drop
+    for SlotReservation<'_, T, N, WAIT>
+{
+    fn drop(&mut self) {
+        for idx in self.remaining() {
+            self.vec.abandon(idx);
+        }
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> Debug
+    for SlotReservation<'_, T, N, WAIT>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlotReservation")
+            .field("remaining", &self.remaining())
+            .finish()
+    }
+}
+
+/// Iterator returned by [`FixSizedVec::iter_mut()`].
+pub struct IterMut<'a, T> {
+    inner: Zip<SliceIterMut<'a, Value<T>>, SliceIterMut<'a, AtomicU8>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (val, state) in self.inner.by_ref() {
+            if *state.get_mut() == READY {
+                // SAFETY:
+                // It is guaranteed to be initialized as the state is `READY`.
+                return Some(unsafe { val.get_mut().assume_init_mut() });
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+impl<'a, T, const N: usize, const WAIT: bool> IntoIterator
+    for &'a mut FixSizedVec<T, N, WAIT>
+{
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Iterator returned by [`FixSizedVec::into_iter()`].
+///
+/// Items that are not consumed are dropped along with it.
+pub struct IntoIter<T, const N: usize, const WAIT: bool = false> {
+    /// The vector, every item that has been moved out gets its state reset,
+    /// so that it won't be dropped again by the vector.
+    vec: FixSizedVec<T, N, WAIT>,
+    /// Index to visit next.
+    idx: usize,
+}
+
+impl<T, const N: usize, const WAIT: bool> Iterator for IntoIter<T, N, WAIT> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (values, states) = self.vec.array.as_mut();
+
+        while self.idx < values.len() {
+            let idx = self.idx;
+            self.idx += 1;
+
+            let state = states[idx].get_mut();
+            if *state == READY {
+                *state = EMPTY;
+                // SAFETY:
+                // It is guaranteed to be initialized as the state is `READY`,
+                // and it is read only once as the state is reset.
+                return Some(unsafe {
+                    values[idx].get_mut().assume_init_read()
+                });
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.vec.capacity() - self.idx))
+    }
+}
+
+impl<T> From<StdVec<T>> for FixSizedVec<T> {
+    /// Create a full vector whose capacity is the length of `vec`.
+    fn from(vec: StdVec<T>) -> Self {
+        let this = Self::with_capacity(vec.len());
+        for val in vec {
+            // It won't be full as the capacity is exactly `vec.len()`.
+            let _ = this.push(val);
+        }
+
+        this
+    }
+}
+
+impl<T> FromIterator<T> for FixSizedVec<T> {
+    /// Create a full vector whose capacity is the number of items.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<StdVec<T>>())
+    }
+}
+
+impl<T, const N: usize, const WAIT: bool> AppendOnlyVec
+    for FixSizedVec<T, N, WAIT>
+{
+    type Item = T;
+    type Iter<'a>
+        = Iter<'a, Self>
+    where
+        Self: 'a;
+
+    fn push(&self, val: T) -> Result<usize, PushError<T>> {
+        FixSizedVec::push(self, val)
+    }
+
+    fn push_get(&self, val: T) -> Result<(usize, &T), PushError<T>> {
+        FixSizedVec::push_get(self, val)
+    }
+
+    fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        FixSizedVec::push_if_len(self, expected, val)
+    }
+
+    fn get(&self, idx: usize) -> Option<&T> {
+        FixSizedVec::get(self, idx)
+    }
+
+    fn len(&self) -> usize {
+        FixSizedVec::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(FixSizedVec::capacity(self))
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        FixSizedVec::iter(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::{Counted, DropCounter},
+        wait::tests::block_on,
+    };
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::Arc,
+        thread::spawn,
+    };
+
+    #[test]
+    fn it_works() {
+        let vec = Arc::new(FixSizedVec::<i32, 25>::new());
+        let mut handles = Vec::new();
+
+        for thread_id in 0..5 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for _ in 0..5 {
+                        vec.push(thread_id).unwrap();
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(vec.len(), 25);
+        let mut counter = [0_usize; 5];
+        for idx in 0..25 {
+            let num = *vec.get(idx).unwrap();
+            counter[num as usize] += 1;
+        }
+
+        for item in counter {
+            assert_eq!(item, 5);
+        }
+    }
+
+    /// Readers spin on `get()` while writers are pushing, every value they see
+    /// has to be fully written, including the heap memory it owns.
+    fn check_visibility<T: Send + Sync + 'static>(
+        make: fn() -> T,
+        check: fn(&T),
+    ) {
+        const WRITERS: usize = 4;
+        const READERS: usize = 4;
+        const LEN: usize = 1024;
+
+        let vec = Arc::new(FixSizedVec::<T, LEN>::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..READERS {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    let mut seen = 0;
+                    while seen < LEN {
+                        seen = 0;
+                        for idx in 0..LEN {
+                            if let Some(val) = vec.get(idx) {
+                                check(val);
+                                seen += 1;
+                            }
+                        }
+                    }
+                }
+            }));
+        }
+
+        for _ in 0..WRITERS {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || while vec.push(make()).is_ok() {}
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn string_visibility() {
+        check_visibility(
+            || "x".repeat(64),
+            |val| {
+                assert_eq!(val.len(), 64);
+                assert!(val.bytes().all(|b| b == b'x'));
+            },
+        );
+    }
+
+    #[test]
+    fn vec_visibility() {
+        check_visibility(
+            || (0..=255_u8).collect::<Vec<u8>>(),
+            |val| {
+                assert_eq!(val.len(), 256);
+                assert!(val.iter().enumerate().all(|(i, b)| i == *b as usize));
+            },
+        );
+    }
+
+    #[test]
+    fn drop_full() {
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::<Counted, 8>::new();
+        for _ in 0..8 {
+            vec.push(counter.item()).unwrap();
+        }
+        // The rejected value is handed back, and dropped with the error
+        let err = vec.push(counter.item()).unwrap_err();
+        assert_eq!(counter.dropped(), 0);
+        drop(err);
+        assert_eq!(counter.dropped(), 1);
+
+        drop(vec);
+        assert_eq!(counter.dropped(), 9);
+    }
+
+    #[test]
+    fn drop_partially_full() {
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::<Counted, 8>::new();
+        for _ in 0..3 {
+            vec.push(counter.item()).unwrap();
+        }
+
+        drop(vec);
+        assert_eq!(counter.dropped(), 3);
+    }
+
+    #[test]
+    fn drop_empty() {
+        let vec = FixSizedVec::<Counted, 8>::new();
+        drop(vec);
+    }
+
+    #[test]
+    fn drop_after_concurrent_pushes() {
+        let counter = DropCounter::default();
+        let vec = Arc::new(FixSizedVec::<Counted, 100>::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..4 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+                let counter = counter.clone();
+
+                move || {
+                    for _ in 0..20 {
+                        vec.push(counter.item()).unwrap();
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(counter.dropped(), 0);
+        drop(vec);
+        assert_eq!(counter.dropped(), 80);
+    }
+
+    #[test]
+    fn push_to_full_vec() {
+        let vec = FixSizedVec::<String, 1>::new();
+        assert_eq!(vec.push(String::from("first")), Ok(0));
+
+        let err = vec.push(String::from("second")).unwrap_err();
+        assert_eq!(err.to_string(), "pushing to a full vector");
+        assert_eq!(err.into_inner(), "second");
+        assert_eq!(vec.len(), 1);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let vec = Arc::new(FixSizedVec::with_capacity(25));
+        assert_eq!(vec.capacity(), 25);
+        let mut handles = Vec::new();
+
+        for thread_id in 0..5 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for _ in 0..5 {
+                        vec.push(thread_id.to_string()).unwrap();
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(vec.len(), 25);
+        assert!(vec.push(String::new()).is_err());
+        assert_eq!(vec.get(25), None);
+        let mut counter = [0_usize; 5];
+        for idx in 0..25 {
+            let num: usize = vec.get(idx).unwrap().parse().unwrap();
+            counter[num] += 1;
+        }
+
+        for item in counter {
+            assert_eq!(item, 5);
+        }
+    }
+
+    #[test]
+    fn with_large_capacity() {
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::with_capacity(1 << 20);
+        for _ in 0..10 {
+            vec.push(counter.item()).unwrap();
+        }
+
+        drop(vec);
+        assert_eq!(counter.dropped(), 10);
+    }
+
+    #[test]
+    fn with_zero_capacity() {
+        let vec = FixSizedVec::with_capacity(0);
+        assert_eq!(vec.push(1), Err(PushError(1)));
+        assert_eq!(vec.get(0), None);
+    }
+
+    #[test]
+    fn len_is_clamped() {
+        let vec = Arc::new(FixSizedVec::<usize, 10>::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..4 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for i in 0..100 {
+                        let _ = vec.push(i);
+                        assert!(vec.len() <= 10);
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(vec.len(), 10);
+        assert!(vec.iter().count() == 10);
+    }
+
+    #[test]
+    fn committed_len_waits_for_holes() {
+        let vec = FixSizedVec::<i32, 4>::new();
+
+        // Reserve index 0 without writing it, like a slow writer.
+        assert_eq!(vec.len.fetch_add(1, Ordering::Relaxed), 0);
+        assert_eq!(vec.push(1), Ok(1));
+        assert_eq!(vec.push(2), Ok(2));
+        assert_eq!(vec.len(), 3);
+        assert_eq!(vec.committed_len(), 0);
+        assert_eq!(vec.get(1), Some(&1));
+        assert_eq!(vec.get_committed(1), None);
+
+        // Fill the hole, the whole prefix becomes committed.
+        let (val, state) = vec.slot(0);
+        // SAFETY:
+        // Index 0 is reserved above and never written.
+        unsafe { (*val.get()).write(0) };
+        state.store(READY, Ordering::Release);
+        assert_eq!(vec.committed_len(), 3);
+        assert_eq!(vec.get_committed(1), Some(&1));
+        assert_eq!(vec.get_committed(3), None);
+        assert_eq!(vec.as_slice(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn as_slice() {
+        let vec = FixSizedVec::with_capacity(4);
+        assert!(vec.as_slice().is_empty());
+
+        vec.push(String::from("a")).unwrap();
+        vec.push(String::from("b")).unwrap();
+        let slice = vec.as_slice();
+        vec.push(String::from("c")).unwrap();
+
+        assert_eq!(slice, ["a", "b"]);
+        assert_eq!(vec.as_slice(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn committed_prefix_is_readable() {
+        const LEN: usize = 4096;
+        let vec = Arc::new(FixSizedVec::<usize, LEN>::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..2 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || loop {
+                    let committed = vec.committed_len();
+                    assert!(committed <= vec.len());
+                    for idx in 0..committed {
+                        assert!(vec.get_committed(idx).is_some());
+                    }
+                    if committed == LEN {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        for _ in 0..4 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || while vec.push(0).is_ok() {}
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn iter() {
+        let vec = FixSizedVec::<i32, 4>::new();
+        assert_eq!(vec.iter().next(), None);
+
+        // Reserve index 1 without writing it, it is skipped.
+        vec.push(0).unwrap();
+        vec.len.fetch_add(1, Ordering::Relaxed);
+        vec.push(2).unwrap();
+
+        let iter = vec.iter();
+        // Not visited as it is pushed after the iterator is created.
+        vec.push(3).unwrap();
+        assert!(iter.copied().eq([0, 2]));
+        assert!((&vec).into_iter().copied().eq([0, 2, 3]));
+    }
+
+    #[test]
+    fn into_iter() {
+        let vec = FixSizedVec::<String, 4>::new();
+        vec.push(String::from("a")).unwrap();
+        // Reserve index 1 without writing it, it is skipped.
+        vec.len.fetch_add(1, Ordering::Relaxed);
+        vec.push(String::from("c")).unwrap();
+
+        assert_eq!(vec.into_vec(), ["a", "c"]);
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::<Counted, 8>::new();
+        for _ in 0..5 {
+            vec.push(counter.item()).unwrap();
+        }
+
+        let mut iter = vec.into_iter();
+        let first = iter.next().unwrap();
+        let second = iter.next().unwrap();
+        drop(iter);
+        assert_eq!(counter.dropped(), 3);
+
+        drop((first, second));
+        assert_eq!(counter.dropped(), 5);
+    }
+
+    #[test]
+    fn from_vec() {
+        let vec = FixSizedVec::from(vec![1, 2, 3]);
+        assert_eq!(vec.capacity(), 3);
+        assert_eq!(vec.as_slice(), [1, 2, 3]);
+        assert!(vec.push(4).is_err());
+
+        let vec: FixSizedVec<i32> = (0..10).collect();
+        assert_eq!(vec.len(), 10);
+        assert!(vec.into_iter().eq(0..10));
+    }
+
+    #[test]
+    fn mutate_with_exclusive_access() {
+        let mut vec = FixSizedVec::<i32, 8>::new();
+        for i in 0..5 {
+            vec.push(i).unwrap();
+        }
+        *vec.get_mut(2).unwrap() = 20;
+        assert_eq!(vec.get_mut(5), None);
+        assert_eq!(vec.get_mut(8), None);
+        for val in &mut vec {
+            *val += 1;
+        }
+        assert_eq!(vec.as_slice(), [1, 2, 21, 4, 5]);
+
+        vec.truncate(10);
+        assert_eq!(vec.len(), 5);
+        vec.truncate(2);
+        assert_eq!(vec.as_slice(), [1, 2]);
+        assert_eq!(vec.get(2), None);
+        assert_eq!(vec.push(3), Ok(2));
+        assert_eq!(vec.as_slice(), [1, 2, 3]);
+
+        vec.clear();
+        assert_eq!(vec.len(), 0);
+        assert_eq!(vec.committed_len(), 0);
+        assert_eq!(vec.get(0), None);
+        assert_eq!(vec.push(1), Ok(0));
+        assert_eq!(vec.as_slice(), [1]);
+    }
+
+    #[test]
+    fn truncate_drops_the_rest() {
+        let counter = DropCounter::default();
+        let mut vec = FixSizedVec::<Counted, 8>::new();
+        for _ in 0..8 {
+            vec.push(counter.item()).unwrap();
+        }
+        // A failed push makes `len` overshoot the capacity.
+        assert!(vec.push(counter.item()).is_err());
+        assert_eq!(counter.dropped(), 1);
+
+        vec.truncate(3);
+        assert_eq!(counter.dropped(), 6);
+        vec.clear();
+        assert_eq!(counter.dropped(), 9);
+        vec.push(counter.item()).unwrap();
+        drop(vec);
+        assert_eq!(counter.dropped(), 10);
+    }
+
+    #[test]
+    fn in_static() {
+        static TABLE: FixSizedVec<usize, 64> = FixSizedVec::new();
+
+        let handles = (0..4)
+            .map(|thread_id| {
+                spawn(move || {
+                    for _ in 0..16 {
+                        TABLE.push(thread_id).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(TABLE.committed_len(), 64);
+        assert_eq!(TABLE.iter().sum::<usize>(), 16 * (1 + 2 + 3));
+    }
+
+    #[test]
+    fn reserve() {
+        let vec = FixSizedVec::<usize, 10>::new();
+        let reservations = [vec.reserve(3).unwrap(), vec.reserve(4).unwrap()];
+        assert_eq!(vec.push(7), Ok(7));
+        assert!(vec.reserve(3).is_none());
+        assert!(vec.reserve(usize::MAX).is_none());
+        assert_eq!(vec.len(), 8);
+        assert_eq!(vec.committed_len(), 0);
+
+        // Fill the later reservation first, on other threads.
+        std::thread::scope(|s| {
+            for mut reservation in reservations.into_iter().rev() {
+                s.spawn(move || {
+                    for idx in reservation.remaining() {
+                        assert_eq!(reservation.write(idx), Ok(idx));
+                    }
+                    assert_eq!(reservation.write(0), Err(PushError(0)));
+                });
+            }
+        });
+
+        assert!(vec.as_slice().iter().copied().eq(0..8));
+        let mut rest = vec.reserve(2).unwrap();
+        assert_eq!(rest.remaining(), 8..10);
+        assert_eq!(rest.write(8), Ok(8));
+        // Index 9 is never written.
+        assert_eq!(vec.len(), 10);
+        assert_eq!(vec.committed_len(), 9);
+        assert!(vec.reserve(1).is_none());
+        assert!(vec.reserve(0).is_some());
+    }
+
+    #[test]
+    fn push_many() {
+        let vec = FixSizedVec::<usize, 8>::new();
+        assert_eq!(vec.push_many(0..3).ok(), Some(0..3));
+        assert_eq!(vec.push_many(Vec::new()).ok(), Some(3..3));
+        assert_eq!(vec.push(3), Ok(3));
+        let err = vec.push_many(4..9).unwrap_err();
+        assert!(err.into_inner().eq(4..9));
+        assert_eq!(vec.push_many(4..8).ok(), Some(4..8));
+        assert!(vec.as_slice().iter().copied().eq(0..8));
+        assert!((0..8).all(|idx| !vec.is_abandoned(idx)));
+    }
+
+    #[test]
+    fn push_many_panics() {
+        /// Claim to have 4 items, but panic on the third one.
+        struct Panicking(usize, DropCounter);
+
+        impl Iterator for Panicking {
+            type Item = Counted;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0 += 1;
+                assert!(self.0 < 3, "producer failed");
+                Some(self.1.item())
+            }
+        }
+
+        impl ExactSizeIterator for Panicking {
+            fn len(&self) -> usize {
+                4
+            }
+        }
+
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::<Counted, 8>::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            vec.push_many(Panicking(0, counter.clone()))
+        }));
+        assert!(result.is_err());
+
+        assert_eq!(vec.len(), 4);
+        assert_eq!(vec.committed_len(), 2);
+        assert!(!vec.is_abandoned(1));
+        assert!(vec.is_abandoned(2));
+        assert!(vec.is_abandoned(3));
+        assert!(vec.get(2).is_none());
+        assert!(!vec.is_abandoned(8));
+
+        // The committed prefix stops at the holes for good.
+        vec.push(counter.item()).unwrap();
+        assert_eq!(vec.committed_len(), 2);
+        assert_eq!(counter.dropped(), 0);
+
+        drop(vec);
+        assert_eq!(counter.dropped(), 3);
+    }
+
+    #[test]
+    fn truncate_reuses_abandoned_indexes() {
+        let mut vec = FixSizedVec::<usize, 4>::new();
+        vec.push(0).unwrap();
+        drop(vec.reserve(2));
+        assert!(vec.is_abandoned(1));
+
+        vec.truncate(1);
+        assert!(!vec.is_abandoned(1));
+        assert_eq!(vec.push_many([1, 2, 3]).ok(), Some(1..4));
+        assert_eq!(vec.as_slice(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn slot_state() {
+        let vec = FixSizedVec::<String, 4>::new();
+        assert_eq!(vec.slot_state(0), SlotState::Empty);
+        assert_eq!(vec.slot_state(usize::MAX), SlotState::OutOfBounds);
+
+        let mut reservation = vec.reserve(3).unwrap();
+        assert_eq!(vec.slot_state(0), SlotState::Pending);
+        assert_eq!(vec.slot_state(3), SlotState::Empty);
+
+        reservation.write("a".to_string()).unwrap();
+        assert_eq!(vec.slot_state(0), SlotState::Ready(&"a".to_string()));
+        assert_eq!(vec.slot_state(1), SlotState::Pending);
+
+        drop(reservation);
+        assert_eq!(vec.slot_state(1), SlotState::Abandoned);
+        assert_eq!(vec.slot_state(2), SlotState::Abandoned);
+
+        vec.push("b".to_string()).unwrap();
+        assert_eq!(vec.slot_state(3), SlotState::Ready(&"b".to_string()));
+        // Failed pushes don't change anything.
+        assert!(vec.push("c".to_string()).is_err());
+        assert_eq!(vec.slot_state(4), SlotState::OutOfBounds);
+    }
+
+    #[test]
+    fn push_with() {
+        let counter = DropCounter::default();
+        let vec = FixSizedVec::<Counted, 3>::new();
+        assert_eq!(vec.push_with(|| counter.item()).ok(), Some(0));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            vec.push_with(|| -> Counted { panic!("constructor failed") })
+        }));
+        assert!(result.is_err());
+        assert!(vec.is_abandoned(1));
+        assert_eq!(vec.committed_len(), 1);
+
+        assert_eq!(vec.push_with(|| counter.item()).ok(), Some(2));
+        // `f` is not called when the vector is full.
+        assert!(vec.push_with(|| unreachable!()).is_err());
+        assert_eq!(counter.dropped(), 0);
+        drop(vec);
+        assert_eq!(counter.dropped(), 2);
+    }
+
+    #[test]
+    fn wait_for() {
+        let vec = FixSizedVec::<usize, 4, true>::new();
+        assert_eq!(vec.wait_for_timeout(0, Duration::from_millis(10)), None);
+        assert_eq!(vec.wait_for(4), None);
+        assert_eq!(vec.wait_len_at_least(5), None);
+
+        std::thread::scope(|s| {
+            let vec = &vec;
+            let waiters = (0..3)
+                .map(|idx| s.spawn(move || vec.wait_for(idx).copied()))
+                .collect::<Vec<_>>();
+            let len_waiter = s.spawn(|| vec.wait_len_at_least(2));
+            let stuck = s.spawn(|| vec.wait_len_at_least(4));
+
+            s.spawn(|| {
+                let mut reservation = vec.reserve(3).unwrap();
+                // Publish index 3 before the reserved ones.
+                let (idx, _) = vec.push_get(1).unwrap();
+                assert_eq!(idx, 3);
+                reservation.write(0).unwrap();
+                reservation.write(1).unwrap();
+            });
+
+            let values = waiters
+                .into_iter()
+                .map(|waiter| waiter.join().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(values, [Some(0), Some(1), None]);
+            assert!(len_waiter.join().unwrap() >= Some(2));
+            // Index 2 is abandoned.
+            assert_eq!(stuck.join().unwrap(), None);
+        });
+        assert_eq!(vec.wait_len_at_least(2), Some(2));
+        assert_eq!(vec.wait_for(3), Some(&1));
+        assert_eq!(vec.wait_for_timeout(3, Duration::MAX), Some(&1));
+        assert_eq!(vec.wait_len_at_least_timeout(2, Duration::MAX), Some(2));
+    }
+
+    #[test]
+    fn only_watched_entries_are_marked() {
+        let vec = FixSizedVec::<usize, 4, true>::new();
+        vec.push(0).unwrap();
+        assert_eq!(vec.slot(0).1.load(Ordering::Relaxed), READY);
+        assert_eq!(vec.slot(1).1.load(Ordering::Relaxed), EMPTY);
+
+        // The waiter that gives up leaves the mark behind, the entry can
+        // still be written.
+        assert_eq!(vec.wait_for_timeout(1, Duration::from_millis(10)), None);
+        assert_eq!(vec.slot(1).1.load(Ordering::Relaxed), WAITING);
+        assert_eq!(vec.slot_state(1), SlotState::Empty);
+        vec.push(1).unwrap();
+        assert_eq!(vec.wait_for(1), Some(&1));
+    }
+
+    #[test]
+    fn wait_for_async() {
+        let vec = FixSizedVec::<usize, 4, true>::new();
+        assert_eq!(block_on(vec.wait_for_async(4)), None);
+
+        std::thread::scope(|s| {
+            let vec = &vec;
+            let waiters = (0..3)
+                .map(|idx| {
+                    s.spawn(move || block_on(vec.wait_for_async(idx)).copied())
+                })
+                .collect::<Vec<_>>();
+
+            s.spawn(|| {
+                let mut reservation = vec.reserve(3).unwrap();
+                reservation.write(0).unwrap();
+                reservation.write(1).unwrap();
+            });
+
+            let values = waiters
+                .into_iter()
+                .map(|waiter| waiter.join().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(values, [Some(0), Some(1), None]);
+        });
+    }
+
+    #[test]
+    fn cursor() {
+        let vec = FixSizedVec::<usize, 100, true>::new();
+        let mut cursor = vec.cursor();
+        assert_eq!(cursor.try_next(), None);
+        assert_eq!(cursor.next_timeout(Duration::from_millis(10)), None);
+
+        let mut reservation = vec.reserve(3).unwrap();
+        reservation.write(0).unwrap();
+        vec.push(3).unwrap();
+        assert_eq!(cursor.next_timeout(Duration::MAX), Some(&0));
+        // Stops at index 1, although index 3 is written.
+        assert_eq!(cursor.try_next(), None);
+        assert_eq!(cursor.position(), 1);
+        // Skips the abandoned indexes.
+        drop(reservation);
+        assert_eq!(cursor.try_next(), Some(&3));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 4..100 {
+                    vec.push(i).unwrap();
+                }
+            });
+
+            for i in 4..50 {
+                assert_eq!(cursor.next_blocking(), Some(&i));
+            }
+            for i in 50..100 {
+                assert_eq!(block_on(cursor.next_async()), Some(&i));
+            }
+        });
+        assert_eq!(cursor.next_blocking(), None);
+        assert_eq!(block_on(cursor.next_async()), None);
+        assert_eq!(cursor.position(), 100);
     }
 }
@@ -2,5 +2,332 @@
 #![allow(clippy::new_without_default)]
 #![allow(clippy::len_without_is_empty)]
 
+pub mod boxcar;
+pub mod broadcast;
+pub mod error;
 pub mod fix_sized;
 pub mod linked_list;
+mod wait;
+
+use error::{PushError, PushIfLenError};
+
+/// Operations shared by all the append-only vectors in this crate, so that
+/// code can be generic over the backing strategy.
+///
+/// ```
+/// use demystify_boxcar::{
+///     boxcar::BoxcarVec, fix_sized::FixSizedVec, linked_list::LinkedListVec,
+///     AppendOnlyVec,
+/// };
+///
+/// fn fill<V: AppendOnlyVec<Item = i32>>(vec: &V) -> i32 {
+///     for i in 0..3 {
+///         vec.push(i).unwrap();
+///     }
+///     vec.iter().sum()
+/// }
+///
+/// assert_eq!(fill(&FixSizedVec::<i32, 3>::new()), 3);
+/// assert_eq!(fill(&LinkedListVec::new()), 3);
+/// assert_eq!(fill(&BoxcarVec::new()), 3);
+/// ```
+pub trait AppendOnlyVec {
+    /// Type of the stored items.
+    type Item;
+
+    /// Iterator returned by [`AppendOnlyVec::iter()`].
+    type Iter<'a>: Iterator<Item = &'a Self::Item>
+    where
+        Self: 'a;
+
+    /// Push an item to it
+    ///
+    /// Return `Ok(index)` when it is successfully written to `index`, or
+    /// [`PushError`] that carries `val` back when the vector is full, which
+    /// never happens for unbounded vectors.
+    ///
+    /// The returned index never changes, so it can be used as a stable ID.
+    fn push(&self, val: Self::Item) -> Result<usize, PushError<Self::Item>>;
+
+    /// Same as [`AppendOnlyVec::push()`], but also return a reference to the
+    /// stored item.
+    fn push_get(
+        &self,
+        val: Self::Item,
+    ) -> Result<(usize, &Self::Item), PushError<Self::Item>>;
+
+    /// Push an item only if the length is `expected`, so that it is written
+    /// to index `expected`, e.g., to append an event only if the log is still
+    /// at the expected version.
+    ///
+    /// Return [`PushIfLenError`] that carries `val` back otherwise, along
+    /// with the current length if that is not `expected`.
+    fn push_if_len(
+        &self,
+        expected: usize,
+        val: Self::Item,
+    ) -> Result<usize, PushIfLenError<Self::Item>>;
+
+    /// Get the value at `idx`, `None` if it is not written yet.
+    fn get(&self, idx: usize) -> Option<&Self::Item>;
+
+    /// Return the length.
+    ///
+    /// It is inaccurate due to concurrent appends.
+    fn len(&self) -> usize;
+
+    /// Return the maximum number of items it can hold, `None` if it is
+    /// unbounded.
+    fn capacity(&self) -> Option<usize>;
+
+    /// Iterate over the items that are written.
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+/// An iterator that visits the items of an [`AppendOnlyVec`] by index.
+///
+/// The length is taken when it gets created, items pushed after that won't be
+/// visited, and indexes that are not written yet are skipped.
+pub struct Iter<'a, V: ?Sized> {
+    vec: &'a V,
+    idx: usize,
+    len: usize,
+}
+
+impl<'a, V: AppendOnlyVec + ?Sized> Iter<'a, V> {
+    /// Create an iterator over the first `vec.len()` items.
+    pub fn new(vec: &'a V) -> Self {
+        Self {
+            vec,
+            idx: 0,
+            len: vec.len(),
+        }
+    }
+}
+
+impl<'a, V: AppendOnlyVec + ?Sized> Iterator for Iter<'a, V> {
+    type Item = &'a V::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.idx < self.len {
+            let idx = self.idx;
+            self.idx += 1;
+
+            if let Some(val) = self.vec.get(idx) {
+                return Some(val);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len - self.idx))
+    }
+}
+
+/// The vectors can only be shared between threads if `T: Send + Sync`, and
+/// sent to another thread if `T: Send`, every case below has to be rejected.
+///
+/// `Rc` is neither `Send` nor `Sync`:
+///
+/// ```compile_fail
+/// use demystify_boxcar::boxcar::BoxcarVec;
+/// use std::rc::Rc;
+///
+/// let vec = BoxcarVec::<Rc<i32>>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+///         let _ = vec.push(Rc::new(1));
+///     });
+/// });
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::fix_sized::FixSizedVec;
+/// use std::rc::Rc;
+///
+/// let vec = FixSizedVec::<Rc<i32>, 4>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+///         let _ = vec.push(Rc::new(1));
+///     });
+/// });
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::linked_list::LinkedListVec;
+/// use std::rc::Rc;
+///
+/// let vec = LinkedListVec::<Rc<i32>>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| {
+///         let _ = vec.push(Rc::new(1));
+///     });
+/// });
+/// ```
+///
+/// `Cell` is `Send` but not `Sync`:
+///
+/// ```compile_fail
+/// use demystify_boxcar::boxcar::BoxcarVec;
+/// use std::cell::Cell;
+///
+/// let vec = BoxcarVec::<Cell<i32>>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| vec.get(0).map(|cell| cell.set(1)));
+/// });
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::fix_sized::FixSizedVec;
+/// use std::cell::Cell;
+///
+/// let vec = FixSizedVec::<Cell<i32>, 4>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| vec.get(0).map(|cell| cell.set(1)));
+/// });
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::linked_list::LinkedListVec;
+/// use std::cell::Cell;
+///
+/// let vec = LinkedListVec::<Cell<i32>>::new();
+/// std::thread::scope(|s| {
+///     s.spawn(|| vec.get(0).map(|cell| cell.set(1)));
+/// });
+/// ```
+///
+/// And `Rc` cannot be sent to another thread along with the vector:
+///
+/// ```compile_fail
+/// use demystify_boxcar::boxcar::BoxcarVec;
+/// use std::rc::Rc;
+///
+/// let vec = BoxcarVec::<Rc<i32>>::new();
+/// std::thread::spawn(move || drop(vec));
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::fix_sized::FixSizedVec;
+/// use std::rc::Rc;
+///
+/// let vec = FixSizedVec::<Rc<i32>, 4>::new();
+/// std::thread::spawn(move || drop(vec));
+/// ```
+///
+/// ```compile_fail
+/// use demystify_boxcar::linked_list::LinkedListVec;
+/// use std::rc::Rc;
+///
+/// let vec = LinkedListVec::<Rc<i32>>::new();
+/// std::thread::spawn(move || drop(vec));
+/// ```
+#[cfg(doctest)]
+pub struct ThreadSafety;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        boxcar::BoxcarVec, fix_sized::FixSizedVec, linked_list::LinkedListVec,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Counts how many of the items it hands out are dropped.
+    #[derive(Clone, Default)]
+    pub(crate) struct DropCounter(Arc<AtomicUsize>);
+
+    impl DropCounter {
+        /// Return an item that increments the counter when dropped.
+        pub(crate) fn item(&self) -> Counted {
+            Counted(self.clone())
+        }
+
+        /// Return the number of items dropped so far.
+        pub(crate) fn dropped(&self) -> usize {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Item returned by [`DropCounter::item()`].
+    pub(crate) struct Counted(DropCounter);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            (self.0).0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn check<V: AppendOnlyVec<Item = usize>>(vec: V) {
+        let capacity = vec.capacity().unwrap_or(100);
+        for idx in 0..capacity {
+            match idx % 3 {
+                0 => assert_eq!(vec.push(idx), Ok(idx)),
+                1 => assert_eq!(vec.push_get(idx), Ok((idx, &idx))),
+                _ => assert_eq!(vec.push_if_len(idx, idx), Ok(idx)),
+            }
+        }
+
+        if vec.capacity().is_some() {
+            assert_eq!(vec.push(capacity), Err(PushError(capacity)));
+            assert_eq!(
+                vec.push_if_len(capacity, capacity),
+                Err(PushIfLenError::Full(capacity))
+            );
+        }
+        assert_eq!(
+            vec.push_if_len(capacity - 1, 0),
+            Err(PushIfLenError::Conflict {
+                len: capacity,
+                value: 0
+            })
+        );
+        assert_eq!(vec.len(), capacity);
+        assert_eq!(vec.get(capacity - 1), Some(&(capacity - 1)));
+        assert_eq!(vec.get(capacity), None);
+        assert!(vec.iter().copied().eq(0..capacity));
+    }
+
+    #[test]
+    fn every_vec_implements_it() {
+        check(FixSizedVec::<usize, 10>::new());
+        check(LinkedListVec::new());
+        check(BoxcarVec::new());
+    }
+
+    /// Only one of the writers that expect the same length wins.
+    fn check_push_if_len<V: AppendOnlyVec<Item = usize> + Sync>(vec: V) {
+        std::thread::scope(|s| {
+            for thread_id in 0..4 {
+                let vec = &vec;
+                s.spawn(move || {
+                    for expected in 0..10 {
+                        match vec.push_if_len(expected, thread_id) {
+                            Ok(idx) => assert_eq!(idx, expected),
+                            Err(PushIfLenError::Conflict { len, value }) => {
+                                assert!(len > expected);
+                                assert_eq!(value, thread_id);
+                            }
+                            Err(PushIfLenError::Full(_)) => unreachable!(),
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(vec.len(), 10);
+        assert_eq!(vec.iter().count(), 10);
+    }
+
+    #[test]
+    fn push_if_len() {
+        check_push_if_len(FixSizedVec::<usize, 10>::new());
+        check_push_if_len(LinkedListVec::new());
+        check_push_if_len(BoxcarVec::new());
+    }
+}
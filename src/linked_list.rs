@@ -1,7 +1,18 @@
+use crate::{
+    error::{PushError, PushIfLenError},
+    wait::{WaitQueue, WakerSlot},
+    AppendOnlyVec,
+};
 use std::{
     fmt::{Debug, Formatter},
-    ptr::null_mut,
+    future::Future,
+    iter::FusedIterator,
+    marker::PhantomData,
+    pin::Pin,
+    ptr::{addr_of_mut, null_mut},
     sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    task::{Context, Poll},
+    time::{Duration, Instant},
     vec::Vec as StdVec,
 };
 
@@ -10,47 +21,49 @@ use std::{
 /// # Random Access
 /// A vector should support random access, but linked list cannot do this, this
 /// is why the vector word is quoted.
+///
+/// # Thread Safety
+///
+/// It can be shared between threads only if `T: Send + Sync`, as other threads
+/// can push `T`s into it and get `&T`s out of it.
 pub struct LinkedListVec<T> {
     head: AtomicPtr<Node<T>>,
     tail: AtomicPtr<Node<T>>,
+    /// The length, with the `WAITING` flag folded into its highest bit.
     len: AtomicUsize,
+    /// Threads blocked in `wait_for()` or `wait_len_at_least()`, and tasks
+    /// waiting on `wait_for_async()`, they are notified by the pushes that
+    /// see the `WAITING` flag.
+    waiters: WaitQueue,
 }
 
+/// Flag in `LinkedListVec::len`: someone is waiting for the length to grow,
+/// so the next push has to notify the waiters, see `WaitQueue`.
+///
+/// The length cannot get there, every item takes a node on the heap.
+const WAITING: usize = 1 << (usize::BITS - 1);
+
 impl<T: Debug> Debug for LinkedListVec<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut vec: Vec<&Node<T>> = StdVec::new();
 
         // Take a snapshot on what is in the Vector, the snapshot is possibly
-        // inaccurate as the structure support concurrent appends.
-        // For example, it can have data `[1, 2]` but length 1.
-        let head = self.head.load(Ordering::Relaxed);
-        let tail = self.tail.load(Ordering::Relaxed);
-        let len = self.len.load(Ordering::Relaxed);
-
-        if !head.is_null() {
-            let mut p = head;
-
-            loop {
-                // SAFETY:
-                //
-                // The raw pointer `p` won't be
-                //    1. NULL as
-                //       * For the first loop, we just checked `!head.is_null()`
-                //       * For the remaining loops, the loop stops at `tail` and
-                //         all the pointers in-between come from `Box::into_raw()`
-                //
-                //    2. dangling as the written pointer comes from `Box::into_raw()`
-                //       and for a written value, we won't modify it.
-                let p_node = unsafe { &*p };
-                vec.push(p_node);
+        // inaccurate as the structure support concurrent appends, we only walk
+        // the first `len` nodes as they are guaranteed to be linked.
+        let len = self.len.load(Ordering::Acquire) & !WAITING;
+        let mut p = self.head.load(Ordering::Acquire);
 
-                if std::ptr::eq(p, tail) {
-                    break;
-                }
-
-                let p_next = p_node.next.load(Ordering::Relaxed);
-                p = p_next;
-            }
+        for _ in 0..len {
+            // SAFETY:
+            //
+            // The raw pointer `p` won't be
+            //    1. NULL as the first `len` nodes are reachable from `head`,
+            //       see `get()`.
+            //    2. dangling as the written pointer comes from `Box::into_raw()`
+            //       and for a written value, we won't modify it.
+            let p_node = unsafe { &*p };
+            vec.push(p_node);
+            p = p_node.next.load(Ordering::Acquire);
         }
 
         f.debug_struct("LinkedListVec")
@@ -63,34 +76,60 @@ impl<T: Debug> Debug for LinkedListVec<T> {
 impl<T> Drop for LinkedListVec<T> {
     fn drop(&mut self) {
         // When dropping, it is guaranteed that no one is accessing the vector,
-        // so this snapshot is accurate.
-        let head = self.head.load(Ordering::Relaxed);
-        let tail = self.tail.load(Ordering::Relaxed);
-
-        if !head.is_null() {
-            let mut p = head;
-
-            loop {
-                // SAFETY:
-                // For pointers stored between `head` and `tail`, they all come
-                // from `Box::into_raw()`, so it is safe to convert it back with
-                // `Box::from_raw()`.
-                let p_node = unsafe { Box::from_raw(p) };
-                let p_next = p_node.next.load(Ordering::Relaxed);
-
-                if std::ptr::eq(p, tail) {
-                    break;
-                }
+        // and every push has finished linking its node, so we can simply
+        // follow the `next` pointers until NULL.
+        //
+        // SAFETY:
+        // The nodes reachable from `head` are owned by the vector, and they
+        // won't be accessed after this.
+        unsafe { free_nodes(*self.head.get_mut()) };
+    }
+}
 
-                p = p_next;
-            }
-        }
+/// Free the node `p` and all the nodes after it.
+///
+/// # Safety
+///
+/// These nodes have to come from `Box::into_raw()`, and they should not be
+/// accessed after this.
+unsafe fn free_nodes<T>(mut p: *mut Node<T>) {
+    while !p.is_null() {
+        // SAFETY:
+        // The caller guarantees that it comes from `Box::into_raw()`, so it is
+        // safe to convert it back with `Box::from_raw()`.
+        let mut p_node = unsafe { Box::from_raw(p) };
+        p = *p_node.next.get_mut();
     }
 }
 
+// SAFETY:
+//
+// `AtomicPtr` is always `Send` and `Sync`, so without these impls the vector
+// would be thread-safe for any `T`, which is wrong as the nodes own `T`s.
+//
+// Sending a `LinkedListVec<T>` to another thread moves all the stored `T`s along
+// with it, so `T` has to be `Send`.
+unsafe impl<T: Send> Send for LinkedListVec<T> {}
+
+// SAFETY:
+//
+// With a `&LinkedListVec<T>`, other threads can
+//
+// 1. push values into it, which moves `T`s across threads, so `T: Send` is
+//    needed.
+// 2. get `&T`s out of it, which shares `T`s across threads, so `T: Sync` is
+//    needed.
+//
+// The linking protocol described in `push()` makes the accesses themselves
+// synchronized.
+unsafe impl<T: Send + Sync> Sync for LinkedListVec<T> {}
+
 /// A node within the linked list.
 struct Node<T> {
     data: T,
+    /// Index of this node, it is set before the node gets linked, and won't
+    /// be changed after that.
+    index: usize,
     next: AtomicPtr<Node<T>>,
 }
 
@@ -105,6 +144,7 @@ impl<T> Node<T> {
     fn new(val: T) -> Self {
         Self {
             data: val,
+            index: 0,
             next: AtomicPtr::new(null_mut()),
         }
     }
@@ -112,59 +152,288 @@ impl<T> Node<T> {
 
 impl<T> LinkedListVec<T> {
     /// Create an empty `LinkedListVec`.
-    pub fn new() -> Self {
+    ///
+    /// It is a `const fn`, so the vector can be put in a `static` item:
+    ///
+    /// ```
+    /// use demystify_boxcar::linked_list::LinkedListVec;
+    ///
+    /// static LOG: LinkedListVec<&str> = LinkedListVec::new();
+    ///
+    /// let idx = LOG.push("event");
+    /// assert_eq!(LOG.get(idx), Some(&"event"));
+    /// ```
+    pub const fn new() -> Self {
         Self {
             head: AtomicPtr::new(null_mut()),
             tail: AtomicPtr::new(null_mut()),
             len: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
         }
     }
 
     /// Push an item to the vector.
-    pub fn push(&self, val: T) {
+    ///
+    /// # Linking protocol
+    ///
+    /// A node is appended by a CAS on its predecessor's `next` pointer (or on
+    /// `head` for the first node), the successful CAS is the moment the node
+    /// becomes part of the list, and it is reachable from `head` right away.
+    /// `tail` is only a hint that points to some node close to the end, it can
+    /// lag behind, in which case threads help to move it forward before
+    /// retrying.
+    ///
+    /// `len` is incremented (with `Release`) only after the node is linked, so
+    /// a reader that loads `len` (with `Acquire`) and sees `n` is guaranteed
+    /// that the first `n` nodes are reachable from `head`.
+    ///
+    /// Return the index the item is written to.
+    pub fn push(&self, val: T) -> usize {
+        self.push_get(val).0
+    }
+
+    /// Same as [`LinkedListVec::push()`], but also return a reference to the
+    /// stored item.
+    pub fn push_get(&self, val: T) -> (usize, &T) {
         let node = Node::new(val);
-        let node_ptr = Box::into_raw(Box::new(node));
-
-        if self
-            .tail
-            .compare_exchange(
-                null_mut(),
-                node_ptr,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            self.head.store(node_ptr, Ordering::Relaxed);
-        } else {
-            loop {
-                let snapshot = self.tail.load(Ordering::Relaxed);
-                assert_ne!(snapshot, null_mut());
+        // SAFETY:
+        // The node is just allocated.
+        unsafe { self.link(Box::into_raw(Box::new(node))) }
+    }
 
-                if self
-                    .tail
+    /// Push the item returned by `f`, which is written to the node directly.
+    ///
+    /// The node is allocated before `f` is called, so for large items, the
+    /// compiler gets the chance to build it in the node, rather than on the
+    /// stack and then copy it in. If `f` panics, the node is freed and nothing
+    /// gets pushed.
+    ///
+    /// Return the index the item is written to.
+    ///
+    /// ```
+    /// use demystify_boxcar::linked_list::LinkedListVec;
+    ///
+    /// let vec = LinkedListVec::<[u8; 4096]>::new();
+    /// let idx = vec.push_with(|| [1; 4096]);
+    /// assert_eq!(vec.get(idx).unwrap()[4095], 1);
+    /// ```
+    pub fn push_with<F: FnOnce() -> T>(&self, f: F) -> usize {
+        let mut node = Box::<Node<T>>::new_uninit();
+        let p = node.as_mut_ptr();
+
+        // SAFETY:
+        // `p` points to the allocated node, every field of it is written in
+        // place exactly once. If `f` panics, `node` is freed as a
+        // `Box<MaybeUninit<_>>`, which drops nothing.
+        let node = unsafe {
+            addr_of_mut!((*p).data).write(f());
+            addr_of_mut!((*p).index).write(0);
+            addr_of_mut!((*p).next).write(AtomicPtr::new(null_mut()));
+            node.assume_init()
+        };
+
+        // SAFETY:
+        // The node is just allocated.
+        unsafe { self.link(Box::into_raw(node)).0 }
+    }
+
+    /// Push an item only if the length is `expected`, so that it is written
+    /// to index `expected`.
+    ///
+    /// The length here is the number of linked nodes, which can be ahead of
+    /// `len()`, as the last node is found by following the `next` pointers.
+    /// Return [`PushIfLenError::Conflict`] that carries that length and `val`
+    /// back if it is not `expected`.
+    ///
+    /// ```
+    /// use demystify_boxcar::{error::PushIfLenError, linked_list::LinkedListVec};
+    ///
+    /// let vec = LinkedListVec::new();
+    /// assert_eq!(vec.push_if_len(0, "a"), Ok(0));
+    /// assert_eq!(
+    ///     vec.push_if_len(0, "b"),
+    ///     Err(PushIfLenError::Conflict { len: 1, value: "b" })
+    /// );
+    /// ```
+    pub fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        let node_ptr = Box::into_raw(Box::new(Node::new(val)));
+
+        loop {
+            let end = self.last_node();
+            let (len, next) = if end.is_null() {
+                (0, &self.head)
+            } else {
+                // SAFETY:
+                // `end` is a linked node, which won't be freed until the
+                // vector is dropped.
+                let end_node = unsafe { &*end };
+                (end_node.index + 1, &end_node.next)
+            };
+
+            if len != expected {
+                // SAFETY:
+                // `node_ptr` comes from `Box::into_raw()` and it is not linked,
+                // so we still own it.
+                let node = unsafe { Box::from_raw(node_ptr) };
+                return Err(PushIfLenError::Conflict {
+                    len,
+                    value: node.data,
+                });
+            }
+
+            // SAFETY:
+            // Same as above, `node_ptr` is not linked yet.
+            unsafe { (*node_ptr).index = expected };
+            if next
+                .compare_exchange(
+                    null_mut(),
+                    node_ptr,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+            {
+                let _ = self.tail.compare_exchange(
+                    end,
+                    node_ptr,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                );
+                break;
+            }
+            // Someone else has linked a node after `end`, it is a conflict,
+            // which is reported with the new length by the next iteration.
+        }
+
+        self.publish();
+
+        Ok(expected)
+    }
+
+    /// Return the last linked node, NULL if the list is empty.
+    ///
+    /// It starts from `tail`, which can lag behind, and follows the `next`
+    /// pointers until the end.
+    fn last_node(&self) -> *mut Node<T> {
+        let mut p = self.tail.load(Ordering::Acquire);
+        if p.is_null() {
+            // `tail` can still be NULL right after the first node is linked.
+            p = self.head.load(Ordering::Acquire);
+            if p.is_null() {
+                return p;
+            }
+        }
+
+        loop {
+            // SAFETY:
+            // `p` is a linked node, which won't be freed until the vector is
+            // dropped.
+            let next = unsafe { &*p }.next.load(Ordering::Acquire);
+            if next.is_null() {
+                return p;
+            }
+            p = next;
+        }
+    }
+
+    /// Append `node_ptr` to the list, following the linking protocol
+    /// described in `push()`.
+    ///
+    /// # Safety
+    ///
+    /// `node_ptr` has to come from `Box::into_raw()`, and it is not linked yet.
+    unsafe fn link(&self, node_ptr: *mut Node<T>) -> (usize, &T) {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+
+            if tail.is_null() {
+                // The list is empty, or the first node has been installed but
+                // `tail` has not been updated yet.
+                //
+                // SAFETY:
+                // `node_ptr` comes from `Box::into_raw()` and it is not linked
+                // yet, so we are the only one that can access it.
+                unsafe { (*node_ptr).index = 0 };
+                match self.head.compare_exchange(
+                    null_mut(),
+                    node_ptr,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        let _ = self.tail.compare_exchange(
+                            null_mut(),
+                            node_ptr,
+                            Ordering::Release,
+                            Ordering::Relaxed,
+                        );
+                        break;
+                    }
+                    Err(head) => {
+                        let _ = self.tail.compare_exchange(
+                            null_mut(),
+                            head,
+                            Ordering::Release,
+                            Ordering::Relaxed,
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            // SAFETY:
+            // The raw pointer `tail` can not be
+            //   1. NULL as we have checked it
+            //   2. dangling as it comes from `Box::into_raw()` and nodes are
+            //      only freed when the vector is dropped
+            //   3. unaligned as it comes from `Box::into_raw()`
+            let tail_node = unsafe { &*tail };
+            let next = tail_node.next.load(Ordering::Acquire);
+
+            if next.is_null() {
+                // SAFETY:
+                // Same as above, `node_ptr` is not linked yet.
+                unsafe { (*node_ptr).index = tail_node.index + 1 };
+                if tail_node
+                    .next
                     .compare_exchange(
-                        snapshot,
+                        null_mut(),
                         node_ptr,
-                        Ordering::Relaxed,
-                        Ordering::Relaxed,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
                     )
                     .is_ok()
                 {
-                    // SAFETY:
-                    // The raw pointer `snapshot` can not be
-                    //   1. NULL as we have checked it `assert_ne!(snapshot, null_mut())`
-                    //   2. dangling as it comes from `Box::into_raw()`
-                    //   3. unaligned as it comes from `Box::into_raw()`
-                    unsafe { &*snapshot }
-                        .next
-                        .store(node_ptr, Ordering::Relaxed);
+                    let _ = self.tail.compare_exchange(
+                        tail,
+                        node_ptr,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                    );
                     break;
                 }
+            } else {
+                // `tail` lags behind, help to move it forward.
+                let _ = self.tail.compare_exchange(
+                    tail,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                );
             }
         }
 
-        self.len.fetch_add(1, Ordering::Relaxed);
+        self.publish();
+
+        // SAFETY:
+        // `node_ptr` is linked, it won't be freed or modified until the vector
+        // is dropped.
+        let node = unsafe { &*node_ptr };
+        (node.index, &node.data)
     }
 
     /// Get the value at the index `idx`.
@@ -179,23 +448,22 @@ impl<T> LinkedListVec<T> {
     /// do this as while loading the value of `len`, the `tail` field can be
     /// updated by other thread, which would return the wrong item.
     pub fn get(&self, idx: usize) -> Option<&T> {
-        let len = self.len.load(Ordering::Relaxed);
+        let len = self.len.load(Ordering::Acquire) & !WAITING;
         if idx >= len {
             return None;
         }
 
-        let mut p = self.head.load(Ordering::Relaxed);
+        let mut p = self.head.load(Ordering::Acquire);
         for _ in 0..idx {
             // SAFETY:
             // The raw pointer `p` it not
-            //   1. NULL
-            //     * For first loop, `self.head` is not NULL as `len` is not 0
-            //     * For remaining loops, `p` can not be NULL as these pointers
-            //       come from `Box::from_raw()`
+            //   1. NULL as the first `len` nodes are guaranteed to be
+            //      reachable from `head`, see the linking protocol described
+            //      in `push()`, and `idx` is smaller than `len`.
             //
             //   2. dangling as it comes from `Box::from_raw()`
             //   2. unaligned as it comes from `Box::from_raw()`
-            p = unsafe { &*p }.next.load(Ordering::Relaxed);
+            p = unsafe { &*p }.next.load(Ordering::Acquire);
         }
 
         // SAFETY:
@@ -209,14 +477,619 @@ impl<T> LinkedListVec<T> {
     /// It is inaccurate due to concurrent appends.
     #[inline]
     pub fn len(&self) -> usize {
-        self.len.load(Ordering::Relaxed)
+        self.len.load(Ordering::Relaxed) & !WAITING
+    }
+
+    /// Count a linked node, and notify the waiters if the `WAITING` flag is
+    /// set.
+    fn publish(&self) {
+        // `Release` so that the nodes below the length can be walked to, see
+        // `get()`, and see `WaitQueue` for the flag.
+        if self.len.fetch_add(1, Ordering::Release) & WAITING != 0 {
+            self.len.fetch_and(!WAITING, Ordering::Relaxed);
+            self.waiters.notify();
+        }
+    }
+
+    /// Set the `WAITING` flag, so that the next push notifies the waiters, and
+    /// return the length.
+    fn watch(&self) -> usize {
+        // `Acquire` for the same reason as in `get()`.
+        self.len.fetch_or(WAITING, Ordering::Acquire) & !WAITING
+    }
+
+    /// Block until the value at `idx` is pushed, and return it.
+    ///
+    /// Return `None` if it will never be pushed, i.e., `idx` is `usize::MAX`,
+    /// as the length cannot get past it.
+    ///
+    /// ```
+    /// use demystify_boxcar::linked_list::LinkedListVec;
+    ///
+    /// let vec = LinkedListVec::new();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| vec.push(1));
+    ///     assert_eq!(vec.wait_for(0), Some(&1));
+    /// });
+    /// ```
+    pub fn wait_for(&self, idx: usize) -> Option<&T> {
+        self.wait_len_at_least(idx.checked_add(1)?);
+        // It won't be `None` as the length is greater than `idx`.
+        self.get(idx)
+    }
+
+    /// Same as [`LinkedListVec::wait_for()`], but also return `None` when
+    /// `timeout` elapses.
+    pub fn wait_for_timeout(
+        &self,
+        idx: usize,
+        timeout: Duration,
+    ) -> Option<&T> {
+        self.wait_len_at_least_timeout(idx.checked_add(1)?, timeout)?;
+        self.get(idx)
+    }
+
+    /// Return a future that resolves to the value at `idx` once it is pushed.
+    ///
+    /// It works with any executor, the task is woken up by the push that
+    /// makes the length greater than `idx`.
+    pub fn wait_for_async(&self, idx: usize) -> WaitFor<'_, T> {
+        WaitFor {
+            vec: self,
+            idx,
+            waker: WakerSlot::new(&self.waiters),
+        }
+    }
+
+    /// Block until the length is at least `n`, and return the length.
+    pub fn wait_len_at_least(&self, n: usize) -> usize {
+        // It won't be `None` as there is no deadline.
+        self.wait_len_at_least_until(n, None).unwrap()
+    }
+
+    /// Same as [`LinkedListVec::wait_len_at_least()`], but return `None` when
+    /// `timeout` elapses.
+    pub fn wait_len_at_least_timeout(
+        &self,
+        n: usize,
+        timeout: Duration,
+    ) -> Option<usize> {
+        // A deadline too far away to be represented is as good as none.
+        self.wait_len_at_least_until(n, Instant::now().checked_add(timeout))
+    }
+
+    fn wait_len_at_least_until(
+        &self,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Option<usize> {
+        self.waiters.wait_until(deadline, || {
+            // `Acquire` so that the nodes below the length can be walked to.
+            let len = self.len.load(Ordering::Acquire) & !WAITING;
+            if len >= n {
+                return Some(len);
+            }
+            // Check again after setting the flag.
+            let len = self.watch();
+            (len >= n).then_some(len)
+        })
+    }
+
+    /// Get a mutable reference to the value at the index `idx`.
+    ///
+    /// Same as `get()`, it has to walk from `head`.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        if idx >= *self.len.get_mut() & !WAITING {
+            return None;
+        }
+
+        let mut p = *self.head.get_mut();
+        for _ in 0..idx {
+            // SAFETY:
+            // `p` is one of the first `len` nodes, so it is valid, and we have
+            // exclusive access to it.
+            p = *unsafe { &mut *p }.next.get_mut();
+        }
+
+        // SAFETY:
+        // Same as above.
+        Some(&mut unsafe { &mut *p }.data)
+    }
+
+    /// Iterate over the items, with mutable references.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: *self.head.get_mut(),
+            remaining: *self.len.get_mut() & !WAITING,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Shorten the vector to `len` items, the rest are unlinked and dropped.
+    ///
+    /// It has no effect if `len` is not smaller than the current length. The
+    /// vector can be pushed to again after this, the first pushed item is
+    /// written to index `len`.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= *self.len.get_mut() & !WAITING {
+            return;
+        }
+
+        let rest = if len == 0 {
+            *self.tail.get_mut() = null_mut();
+            std::mem::replace(self.head.get_mut(), null_mut())
+        } else {
+            let mut p = *self.head.get_mut();
+            for _ in 0..len - 1 {
+                // SAFETY:
+                // `p` is one of the first `len` nodes, so it is valid, and we
+                // have exclusive access to it.
+                p = *unsafe { &mut *p }.next.get_mut();
+            }
+
+            // `p` becomes the last node.
+            *self.tail.get_mut() = p;
+            // SAFETY:
+            // Same as above.
+            std::mem::replace(unsafe { &mut *p }.next.get_mut(), null_mut())
+        };
+        *self.len.get_mut() = len;
+
+        // SAFETY:
+        // `rest` and the nodes after it are unlinked, no one can access them.
+        unsafe { free_nodes(rest) };
+    }
+
+    /// Remove all the items.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Move the items into a `std::vec::Vec` without cloning them.
+    pub fn into_vec(self) -> StdVec<T> {
+        self.into_iter().collect()
+    }
+
+    /// Iterate over the items.
+    ///
+    /// The length is taken when it gets created, items pushed after that won't
+    /// be visited. Unlike calling `get()` with every index, it follows the
+    /// `next` pointers, so visiting all the items is O(n).
+    pub fn iter(&self) -> Iter<'_, T> {
+        // `len` has to be loaded before `head`, otherwise we may see a NULL
+        // `head` and then a non-zero `len` pushed after that.
+        let remaining = self.len.load(Ordering::Acquire) & !WAITING;
+        Iter {
+            next: self.head.load(Ordering::Acquire),
+            remaining,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Return a cursor that starts from index 0, and follows the items as they
+    /// are pushed.
+    ///
+    /// ```
+    /// use demystify_boxcar::linked_list::LinkedListVec;
+    ///
+    /// let vec = LinkedListVec::new();
+    /// let mut cursor = vec.cursor();
+    /// vec.push(1);
+    /// assert_eq!(cursor.try_next(), Some(&1));
+    /// assert_eq!(cursor.try_next(), None);
+    /// vec.push(2);
+    /// assert_eq!(cursor.try_next(), Some(&2));
+    /// ```
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            vec: self,
+            last: null_mut(),
+            position: 0,
+        }
+    }
+}
+
+/// A cursor that yields the items in order, including the ones pushed after
+/// it is created, returned by [`LinkedListVec::cursor()`].
+///
+/// It remembers the last visited node, so every step follows a single `next`
+/// pointer rather than walking from `head`.
+pub struct Cursor<'a, T> {
+    vec: &'a LinkedListVec<T>,
+    /// The last visited node, NULL if nothing has been visited.
+    last: *const Node<T>,
+    /// Index of the item to visit next.
+    position: usize,
+}
+
+impl<'a, T> Cursor<'a, T> {
+    /// Return the index of the item to visit next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Return the next item, or `None` if it is not pushed yet.
+    pub fn try_next(&mut self) -> Option<&'a T> {
+        let next = if self.last.is_null() {
+            self.vec.head.load(Ordering::Acquire)
+        } else {
+            // SAFETY:
+            // `last` is a linked node, and nodes are only freed with exclusive
+            // access to the vector, which cannot happen while it is borrowed
+            // by the cursor.
+            unsafe { &*self.last }.next.load(Ordering::Acquire)
+        };
+        if next.is_null() {
+            return None;
+        }
+
+        // SAFETY:
+        // `next` is linked, its fields are visible to us through the `Acquire`
+        // load above, which pairs with the `AcqRel` CAS that links it, and it
+        // won't be freed for the same reason as above.
+        let node = unsafe { &*next };
+        self.last = next;
+        self.position += 1;
+
+        Some(&node.data)
+    }
+
+    /// Block until the next item is pushed, and return it.
+    pub fn next_blocking(&mut self) -> &'a T {
+        // It won't be `None` as there is no deadline.
+        self.vec
+            .waiters
+            .wait_until(None, || self.watch_next())
+            .unwrap()
+    }
+
+    /// Same as [`Cursor::next_blocking()`], but return `None` when `timeout`
+    /// elapses.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<&'a T> {
+        // Same as `wait_len_at_least_timeout()`, no deadline if it is too far
+        // away.
+        let deadline = Instant::now().checked_add(timeout);
+        self.vec.waiters.wait_until(deadline, || self.watch_next())
+    }
+
+    /// Return a future that resolves to the next item once it is pushed.
+    pub fn next_async(&mut self) -> Next<'_, 'a, T> {
+        Next {
+            waker: WakerSlot::new(&self.vec.waiters),
+            cursor: self,
+        }
+    }
+
+    /// Same as `try_next()`, but set the `WAITING` flag and check again
+    /// before returning `None`, see `LinkedListVec::watch()`.
+    fn watch_next(&mut self) -> Option<&'a T> {
+        self.try_next().or_else(|| {
+            // The node is linked before the length is incremented, the
+            // `Acquire` in `watch()` makes it visible if we come second.
+            self.vec.watch();
+            self.try_next()
+        })
+    }
+}
+
+impl<T> Debug for Cursor<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cursor")
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+// SAFETY:
+// It is equivalent to a `&LinkedListVec<T>`.
+unsafe impl<T: Send + Sync> Send for Cursor<'_, T> {}
+
+// SAFETY:
+// It is equivalent to a `&LinkedListVec<T>`.
+unsafe impl<T: Send + Sync> Sync for Cursor<'_, T> {}
+
+/// Future returned by [`Cursor::next_async()`].
+pub struct Next<'c, 'a, T> {
+    cursor: &'c mut Cursor<'a, T>,
+    waker: WakerSlot<'a>,
+}
+
+impl<'a, T> Future for Next<'_, 'a, T> {
+    type Output = &'a T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let cursor = &mut *this.cursor;
+        this.waker.poll_until(cx, || cursor.watch_next())
+    }
+}
+
+impl<T> Debug for Next<'_, '_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Next")
+            .field("cursor", &self.cursor)
+            .finish()
+    }
+}
+
+/// Future returned by [`LinkedListVec::wait_for_async()`].
+pub struct WaitFor<'a, T> {
+    vec: &'a LinkedListVec<T>,
+    idx: usize,
+    waker: WakerSlot<'a>,
+}
+
+impl<'a, T> Future for WaitFor<'a, T> {
+    type Output = &'a T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (vec, idx) = (this.vec, this.idx);
+        this.waker.poll_until(cx, || {
+            vec.get(idx).or_else(|| {
+                vec.watch();
+                vec.get(idx)
+            })
+        })
+    }
+}
+
+impl<T> Debug for WaitFor<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WaitFor").field("idx", &self.idx).finish()
+    }
+}
+
+/// Iterator returned by [`LinkedListVec::iter()`].
+pub struct Iter<'a, T> {
+    /// The node to visit next.
+    next: *const Node<T>,
+    /// Number of nodes that are left to visit.
+    remaining: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // SAFETY:
+        // The raw pointer `self.next` is not
+        //   1. NULL as it is one of the first `len` nodes, which are guaranteed
+        //      to be reachable from `head`, see `get()`.
+        //   2. dangling as nodes are only freed when the vector is dropped,
+        //      which cannot happen while it is borrowed by the iterator.
+        let node = unsafe { &*self.next };
+        self.next = node.next.load(Ordering::Acquire);
+        self.remaining -= 1;
+
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+// SAFETY:
+// It is equivalent to a `&LinkedListVec<T>`.
+unsafe impl<T: Send + Sync> Send for Iter<'_, T> {}
+
+// SAFETY:
+// It is equivalent to a `&LinkedListVec<T>`.
+unsafe impl<T: Send + Sync> Sync for Iter<'_, T> {}
+
+/// Iterator returned by [`LinkedListVec::iter_mut()`].
+pub struct IterMut<'a, T> {
+    /// The node to visit next.
+    next: *mut Node<T>,
+    /// Number of nodes that are left to visit.
+    remaining: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // SAFETY:
+        // `self.next` is one of the first `len` nodes, so it is valid, the
+        // vector is mutably borrowed by the iterator, and each node is visited
+        // once, so there is no aliasing.
+        let node = unsafe { &mut *self.next };
+        self.next = *node.next.get_mut();
+        self.remaining -= 1;
+
+        Some(&mut node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+impl<T> FusedIterator for IterMut<'_, T> {}
+
+// SAFETY:
+// It is equivalent to a `&mut LinkedListVec<T>`.
+unsafe impl<T: Send> Send for IterMut<'_, T> {}
+
+// SAFETY:
+// It is equivalent to a `&mut LinkedListVec<T>`.
+unsafe impl<T: Sync> Sync for IterMut<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a mut LinkedListVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedListVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for LinkedListVec<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    /// Move the items out of the vector, the nodes are unlinked and freed one
+    /// by one.
+    fn into_iter(mut self) -> Self::IntoIter {
+        // Take the nodes away, so that the vector has nothing to free when it
+        // gets dropped.
+        let head = std::mem::replace(self.head.get_mut(), null_mut());
+        *self.tail.get_mut() = null_mut();
+
+        IntoIter {
+            next: head,
+            remaining: std::mem::take(self.len.get_mut()) & !WAITING,
+        }
+    }
+}
+
+/// Iterator returned by [`LinkedListVec::into_iter()`].
+///
+/// Items that are not consumed are dropped along with it.
+pub struct IntoIter<T> {
+    /// The node to visit next, the iterator owns it and all the nodes after
+    /// it.
+    next: *mut Node<T>,
+    /// Number of nodes that are left to visit.
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next.is_null() {
+            return None;
+        }
+
+        // SAFETY:
+        // The nodes owned by the iterator all come from `Box::into_raw()`, and
+        // each of them is converted back only once as we move to the next.
+        let node = unsafe { Box::from_raw(self.next) };
+        self.next = node.next.load(Ordering::Relaxed);
+        self.remaining -= 1;
+
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+// SAFETY:
+// It owns the remaining items, same as a `LinkedListVec<T>`.
+unsafe impl<T: Send> Send for IntoIter<T> {}
+
+// SAFETY:
+// No item can be accessed through a `&IntoIter<T>`.
+unsafe impl<T> Sync for IntoIter<T> {}
+
+impl<T> From<StdVec<T>> for LinkedListVec<T> {
+    fn from(vec: StdVec<T>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<T> FromIterator<T> for LinkedListVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let this = Self::new();
+        for val in iter {
+            this.push(val);
+        }
+
+        this
+    }
+}
+
+impl<T> AppendOnlyVec for LinkedListVec<T> {
+    type Item = T;
+    type Iter<'a>
+        = Iter<'a, T>
+    where
+        Self: 'a;
+
+    fn push(&self, val: T) -> Result<usize, PushError<T>> {
+        Ok(LinkedListVec::push(self, val))
+    }
+
+    fn push_get(&self, val: T) -> Result<(usize, &T), PushError<T>> {
+        Ok(LinkedListVec::push_get(self, val))
+    }
+
+    fn push_if_len(
+        &self,
+        expected: usize,
+        val: T,
+    ) -> Result<usize, PushIfLenError<T>> {
+        LinkedListVec::push_if_len(self, expected, val)
+    }
+
+    fn get(&self, idx: usize) -> Option<&T> {
+        LinkedListVec::get(self, idx)
+    }
+
+    fn len(&self) -> usize {
+        LinkedListVec::len(self)
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        LinkedListVec::iter(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{sync::Arc, thread::spawn};
+    use crate::{
+        tests::{Counted, DropCounter},
+        wait::tests::block_on,
+    };
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::Arc,
+        thread::spawn,
+    };
 
     #[test]
     fn it_works() {
@@ -250,4 +1123,296 @@ mod tests {
             assert_eq!(item, 5);
         }
     }
+
+    #[test]
+    fn readers_never_see_unlinked_nodes() {
+        const WRITERS: usize = 4;
+        const PER_WRITER: usize = 500;
+        const LEN: usize = WRITERS * PER_WRITER;
+
+        let vec = Arc::new(LinkedListVec::new());
+        let mut handles = Vec::new();
+
+        for _ in 0..4 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || loop {
+                    let len = vec.len();
+                    if len > 0 {
+                        // Every index below `len` has to be reachable.
+                        assert!(vec.get(len - 1).is_some());
+                        let _ = format!("{:?}", vec);
+                    }
+                    if len == LEN {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        for thread_id in 0..WRITERS {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    for _ in 0..PER_WRITER {
+                        vec.push(thread_id);
+                    }
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(vec.len(), LEN);
+    }
+
+    #[test]
+    fn push_returns_index() {
+        let vec = Arc::new(LinkedListVec::new());
+        let mut handles = Vec::new();
+
+        for thread_id in 0..5 {
+            handles.push(spawn({
+                let vec = Arc::clone(&vec);
+
+                move || {
+                    (0..100)
+                        .map(|i| {
+                            let num = thread_id * 100 + i;
+                            let (idx, val) = vec.push_get(num);
+                            assert_eq!(*val, num);
+                            (idx, num)
+                        })
+                        .collect::<Vec<_>>()
+                }
+            }));
+        }
+
+        let mut pushed = Vec::new();
+        for handle in handles {
+            pushed.extend(handle.join().unwrap());
+        }
+
+        for &(idx, num) in &pushed {
+            assert_eq!(vec.get(idx), Some(&num));
+        }
+        pushed.sort();
+        assert!(pushed.into_iter().map(|(idx, _)| idx).eq(0..500));
+    }
+
+    #[test]
+    fn iter() {
+        let vec = LinkedListVec::new();
+        assert_eq!(vec.iter().next(), None);
+
+        for i in 0..10 {
+            vec.push(i);
+        }
+
+        let iter = vec.iter();
+        // Not visited as it is pushed after the iterator is created.
+        vec.push(10);
+        assert_eq!(iter.len(), 10);
+        assert!(iter.copied().eq(0..10));
+        assert!((&vec).into_iter().copied().eq(0..11));
+    }
+
+    #[test]
+    fn iter_while_pushing() {
+        let vec = Arc::new(LinkedListVec::new());
+        let writer = spawn({
+            let vec = Arc::clone(&vec);
+
+            move || {
+                for i in 0..1000 {
+                    vec.push(i);
+                }
+            }
+        });
+
+        while vec.len() < 1000 {
+            // Each item is visited once and in order, as there is only 1
+            // writer.
+            let iter = vec.iter();
+            let len = iter.len();
+            assert!(iter.copied().eq(0..len));
+        }
+
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn into_iter() {
+        let vec: LinkedListVec<String> =
+            vec![String::from("a"), String::from("b")].into();
+        assert_eq!(vec.into_vec(), ["a", "b"]);
+
+        let vec = (0..10).collect::<LinkedListVec<_>>();
+        let iter = vec.into_iter();
+        assert_eq!(iter.len(), 10);
+        assert!(iter.eq(0..10));
+
+        assert_eq!(LinkedListVec::<i32>::new().into_iter().next(), None);
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let counter = DropCounter::default();
+        let vec = LinkedListVec::new();
+        for _ in 0..5 {
+            vec.push(counter.item());
+        }
+
+        let mut iter = vec.into_iter();
+        let first = iter.next().unwrap();
+        drop(iter);
+        assert_eq!(counter.dropped(), 4);
+
+        drop(first);
+        assert_eq!(counter.dropped(), 5);
+    }
+
+    #[test]
+    fn mutate_with_exclusive_access() {
+        let mut vec = (0..5).collect::<LinkedListVec<_>>();
+        *vec.get_mut(2).unwrap() = 20;
+        assert_eq!(vec.get_mut(5), None);
+        for val in &mut vec {
+            *val += 1;
+        }
+        assert!(vec.iter().copied().eq([1, 2, 21, 4, 5]));
+
+        vec.truncate(10);
+        assert_eq!(vec.len(), 5);
+        vec.truncate(2);
+        assert!(vec.iter().copied().eq([1, 2]));
+        assert_eq!(vec.push(3), 2);
+        assert!(vec.iter().copied().eq([1, 2, 3]));
+
+        vec.clear();
+        assert_eq!(vec.len(), 0);
+        assert_eq!(vec.get(0), None);
+        assert_eq!(vec.push(1), 0);
+        assert!(vec.iter().copied().eq([1]));
+    }
+
+    #[test]
+    fn truncate_drops_the_rest() {
+        let counter = DropCounter::default();
+        let mut vec = LinkedListVec::new();
+        for _ in 0..5 {
+            vec.push(counter.item());
+        }
+
+        vec.truncate(3);
+        assert_eq!(counter.dropped(), 2);
+        vec.clear();
+        assert_eq!(counter.dropped(), 5);
+        vec.push(counter.item());
+        drop(vec);
+        assert_eq!(counter.dropped(), 6);
+    }
+
+    #[test]
+    fn push_with() {
+        let counter = DropCounter::default();
+        let vec = LinkedListVec::new();
+        assert_eq!(vec.push_with(|| counter.item()), 0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            vec.push_with(|| -> Counted { panic!("constructor failed") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(vec.len(), 1);
+
+        assert_eq!(vec.push_with(|| counter.item()), 1);
+        assert_eq!(counter.dropped(), 0);
+        drop(vec);
+        assert_eq!(counter.dropped(), 2);
+    }
+
+    #[test]
+    fn wait_for() {
+        let vec = LinkedListVec::new();
+        assert_eq!(
+            vec.wait_len_at_least_timeout(1, Duration::from_millis(10)),
+            None
+        );
+
+        std::thread::scope(|s| {
+            let vec = &vec;
+            let waiters = (0..4)
+                .map(|idx| s.spawn(move || *vec.wait_for(idx).unwrap()))
+                .collect::<Vec<_>>();
+            s.spawn(|| {
+                for i in 0..4 {
+                    vec.push(i);
+                }
+            });
+
+            for (idx, waiter) in waiters.into_iter().enumerate() {
+                assert_eq!(waiter.join().unwrap(), idx);
+            }
+        });
+        assert_eq!(vec.wait_len_at_least(2), 4);
+        assert_eq!(vec.wait_for_timeout(3, Duration::ZERO), Some(&3));
+        assert_eq!(vec.wait_for_timeout(4, Duration::ZERO), None);
+        assert_eq!(vec.wait_for(usize::MAX), None);
+        assert_eq!(vec.wait_for_timeout(usize::MAX, Duration::ZERO), None);
+        assert_eq!(vec.wait_for_timeout(3, Duration::MAX), Some(&3));
+        assert_eq!(vec.wait_len_at_least_timeout(2, Duration::MAX), Some(4));
+    }
+
+    #[test]
+    fn wait_for_async() {
+        let vec = LinkedListVec::new();
+        std::thread::scope(|s| {
+            let vec = &vec;
+            let waiters = (0..4)
+                .map(|idx| s.spawn(move || *block_on(vec.wait_for_async(idx))))
+                .collect::<Vec<_>>();
+            s.spawn(|| {
+                for i in 0..4 {
+                    vec.push(i);
+                }
+            });
+
+            for (idx, waiter) in waiters.into_iter().enumerate() {
+                assert_eq!(waiter.join().unwrap(), idx);
+            }
+        });
+        assert_eq!(block_on(vec.wait_for_async(3)), &3);
+    }
+
+    #[test]
+    fn cursor() {
+        let vec = LinkedListVec::new();
+        vec.push(0);
+        let mut cursor = vec.cursor();
+        assert_eq!(cursor.next_timeout(Duration::MAX), Some(&0));
+        assert_eq!(cursor.try_next(), None);
+        assert_eq!(cursor.next_timeout(Duration::from_millis(10)), None);
+        assert_eq!(cursor.position(), 1);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..100 {
+                    vec.push(i);
+                }
+            });
+
+            for i in 1..50 {
+                assert_eq!(cursor.next_blocking(), &i);
+            }
+            for i in 50..100 {
+                assert_eq!(block_on(cursor.next_async()), &i);
+            }
+        });
+        assert_eq!(cursor.try_next(), None);
+        assert_eq!(cursor.position(), 100);
+    }
 }
@@ -0,0 +1,316 @@
+use std::{
+    mem,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+/// Threads and futures waiting for something to be pushed.
+///
+/// Threads sleep on the condition variable, futures register their wakers in
+/// the list protected by the lock, see [`WakerSlot`].
+///
+/// # Lost wake-ups
+///
+/// The queue itself doesn't know whether anyone is waiting, that is tracked
+/// by the vector with a flag folded into the atomic it publishes a write
+/// with, so that a push doesn't take the lock when no one is waiting:
+///
+/// * A waiter sets the flag with an RMW in `check`, and then tells, from the
+///   value returned by the RMW, whether the write is there.
+/// * A writer publishes with an RMW, and calls `notify()` only if the value
+///   returned by it has the flag set.
+///
+/// Both RMWs are on the same atomic, one of them comes first, so either the
+/// waiter sees the write and returns, or the writer sees the flag and
+/// notifies. The notification cannot slip in between the waiter's check and
+/// its sleep, as the waiter holds the lock through both, and `notify()` takes
+/// the lock.
+///
+/// A writer that sees the flag may clear it before it notifies, the waiters
+/// that still have to wait set it again, with the lock held, before they
+/// sleep, so they won't miss the next write.
+pub(crate) struct WaitQueue {
+    lock: Mutex<Wakers>,
+    cond: Condvar,
+}
+
+/// Wakers registered by futures.
+struct Wakers {
+    /// Key for the next registered waker.
+    next_key: usize,
+    /// Every waker is removed once it is woken up, or the future is dropped.
+    wakers: Vec<(usize, Waker)>,
+}
+
+impl WaitQueue {
+    /// Create a queue with no waiters.
+    pub(crate) const fn new() -> Self {
+        Self {
+            lock: Mutex::new(Wakers {
+                next_key: 0,
+                wakers: Vec::new(),
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Wake up all the waiters.
+    ///
+    /// It should be called after the write is published, by a writer that
+    /// sees the flag set, see [`WaitQueue`].
+    pub(crate) fn notify(&self) {
+        // Taking the lock makes sure that the waiting threads are either
+        // sleeping, or have not checked yet, the same goes for the futures.
+        let wakers = mem::take(&mut self.lock().wakers);
+        self.cond.notify_all();
+
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Block until `check` returns `Some`, or `deadline` is reached, `None`
+    /// for no deadline.
+    ///
+    /// `check` has to set the flag before it returns `None`, see
+    /// [`WaitQueue`], it is called again every time a writer notifies.
+    pub(crate) fn wait_until<R>(
+        &self,
+        deadline: Option<Instant>,
+        mut check: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        if let Some(ret) = check() {
+            return Some(ret);
+        }
+
+        let mut guard = self.lock();
+        loop {
+            if let Some(ret) = check() {
+                return Some(ret);
+            }
+
+            match deadline {
+                None => {
+                    guard = self
+                        .cond
+                        .wait(guard)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    guard = self
+                        .cond
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        }
+    }
+
+    /// Take the lock, it won't be poisoned as nothing can panic while holding
+    /// it, except `check`, which doesn't break anything.
+    fn lock(&self) -> MutexGuard<'_, Wakers> {
+        self.lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The waker registered by a future in a [`WaitQueue`].
+///
+/// It is removed when the future is dropped, so a future that is dropped
+/// before it completes leaves nothing behind.
+pub(crate) struct WakerSlot<'a> {
+    queue: &'a WaitQueue,
+    /// Key of the registered waker, it may have been removed by `notify()`.
+    key: Option<usize>,
+}
+
+impl<'a> WakerSlot<'a> {
+    /// Create a slot with no registered waker.
+    pub(crate) fn new(queue: &'a WaitQueue) -> Self {
+        Self { queue, key: None }
+    }
+
+    /// Return `Ready` if `check` returns `Some`, otherwise register the waker
+    /// of `cx` so that the task is woken up by the next `notify()`.
+    ///
+    /// `check` has the same requirement as in `WaitQueue::wait_until()`, it
+    /// is called again with the lock held after the waker is registered.
+    pub(crate) fn poll_until<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut check: impl FnMut() -> Option<R>,
+    ) -> Poll<R> {
+        if let Some(ret) = check() {
+            self.unregister();
+            return Poll::Ready(ret);
+        }
+
+        let mut guard = self.queue.lock();
+        let wakers = &mut *guard;
+        let registered = self
+            .key
+            .and_then(|key| wakers.wakers.iter_mut().find(|(k, _)| *k == key));
+        match registered {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => {
+                let key = wakers.next_key;
+                wakers.next_key += 1;
+                wakers.wakers.push((key, cx.waker().clone()));
+                self.key = Some(key);
+            }
+        }
+
+        match check() {
+            Some(ret) => {
+                drop(guard);
+                self.unregister();
+                Poll::Ready(ret)
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    /// Remove the registered waker, if it is still there.
+    fn unregister(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+
+        let mut wakers = self.queue.lock();
+        if let Some(pos) = wakers.wakers.iter().position(|(k, _)| *k == key) {
+            wakers.wakers.swap_remove(pos);
+        }
+    }
+}
+
+impl Drop for WakerSlot<'_> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::Wake,
+        thread::{self, sleep, spawn, Thread},
+        time::Duration,
+    };
+
+    /// Wake up a thread by unparking it.
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Run `fut` to completion on the current thread.
+    pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(ret) => return ret,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn wakes_up_waiters() {
+        static QUEUE: WaitQueue = WaitQueue::new();
+        static READY: AtomicBool = AtomicBool::new(false);
+
+        let handles = (0..4)
+            .map(|_| {
+                spawn(|| {
+                    QUEUE.wait_until(None, || {
+                        READY.load(Ordering::Acquire).then_some(())
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        sleep(Duration::from_millis(10));
+        READY.store(true, Ordering::Release);
+        QUEUE.notify();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(()));
+        }
+    }
+
+    #[test]
+    fn timeout() {
+        let queue = WaitQueue::new();
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert_eq!(queue.wait_until(Some(deadline), || None::<()>), None);
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn wakes_up_futures() {
+        static QUEUE: WaitQueue = WaitQueue::new();
+        static READY: AtomicBool = AtomicBool::new(false);
+
+        /// Complete once `READY` is set.
+        struct Ready(WakerSlot<'static>);
+
+        impl Future for Ready {
+            type Output = ();
+
+            fn poll(
+                mut self: std::pin::Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<()> {
+                self.0.poll_until(cx, || {
+                    READY.load(Ordering::Acquire).then_some(())
+                })
+            }
+        }
+
+        let handles = (0..4)
+            .map(|_| spawn(|| block_on(Ready(WakerSlot::new(&QUEUE)))))
+            .collect::<Vec<_>>();
+
+        sleep(Duration::from_millis(10));
+        READY.store(true, Ordering::Release);
+        QUEUE.notify();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(QUEUE.lock().wakers.is_empty());
+    }
+
+    #[test]
+    fn dropped_futures_unregister() {
+        let queue = WaitQueue::new();
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut slot = WakerSlot::new(&queue);
+        assert!(slot.poll_until(&mut cx, || None::<()>).is_pending());
+        // Polling again replaces the waker rather than adding another one.
+        assert!(slot.poll_until(&mut cx, || None::<()>).is_pending());
+        assert_eq!(queue.lock().wakers.len(), 1);
+        drop(slot);
+        assert!(queue.lock().wakers.is_empty());
+    }
+}